@@ -2,6 +2,8 @@ use reqwest::Client;
 use reqwest_eventsource::{Event, EventSource};
 use tokio_stream::StreamExt;
 
+use meddler_core::types::SYSTEM_AGENT;
+
 /// How the agent processes incoming messages.
 pub enum AgentMode {
     /// Echo the message back (for testing).
@@ -64,9 +66,20 @@ pub async fn run(
                 let content = message["content"]
                     .as_str()
                     .unwrap_or_default();
-                let _sender_name = message["sender_id"]
-                    .as_str()
-                    .unwrap_or("unknown");
+                let Some(sender_name) = message["sender_name"].as_str() else {
+                    tracing::warn!("Message has no sender_name, cannot reply");
+                    continue;
+                };
+
+                // Notices from meddler itself need no answer, and neither do
+                // replies: answering those would have two agents go back and
+                // forth forever
+                if sender_name == SYSTEM_AGENT || !message["in_reply_to"].is_null() {
+                    if let Err(e) = acknowledge(&client, meddler_url, name, &message).await {
+                        tracing::warn!("Failed to acknowledge message: {e}");
+                    }
+                    continue;
+                }
 
                 // Generate response
                 let response = match &mode {
@@ -80,18 +93,43 @@ pub async fn run(
 
                 tracing::info!("[sent] {response}");
 
-                // Send response back through meddler
-                // We need to resolve the sender name - for now, we send back to the orchestrator
-                let _ = client
+                // Reply to whoever wrote to us, through meddler
+                let sent = client
                     .post(format!("{meddler_url}/agent/message"))
                     .json(&serde_json::json!({
                         "from": name,
-                        "to": "__orchestrator__",
+                        "to": sender_name,
                         "content": response,
                         "task_id": message.get("task_id").and_then(|v| v.as_str()),
+                        "in_reply_to": message.get("id").and_then(|v| v.as_str()),
                     }))
                     .send()
                     .await;
+                let handled = match sent {
+                    Ok(r) if r.status().is_success() => true,
+                    // Rejected for good (e.g. the task has closed), so having
+                    // the message redelivered wouldn't help
+                    Ok(r) if r.status().is_client_error() => {
+                        tracing::warn!("Reply rejected: {}", r.text().await.unwrap_or_default());
+                        true
+                    }
+                    Ok(r) => {
+                        tracing::warn!("Failed to send reply: {}", r.status());
+                        false
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to send reply: {e}");
+                        false
+                    }
+                };
+
+                // Acknowledge only once the message is dealt with, so a crash
+                // or server error before this point gets it redelivered
+                if handled {
+                    if let Err(e) = acknowledge(&client, meddler_url, name, &message).await {
+                        tracing::warn!("Failed to acknowledge message: {e}");
+                    }
+                }
             }
             Err(err) => {
                 tracing::error!("SSE error: {err}");
@@ -104,6 +142,30 @@ pub async fn run(
     Ok(())
 }
 
+/// Tell meddler a message has been processed.
+pub async fn acknowledge(
+    client: &Client,
+    meddler_url: &str,
+    name: &str,
+    message: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let message_id = message["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("message has no id"))?;
+
+    client
+        .post(format!("{meddler_url}/agent/ack"))
+        .json(&serde_json::json!({
+            "agent": name,
+            "message_id": message_id,
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
 /// Call an OpenAI-compatible LLM API.
 async fn call_llm(
     client: &Client,
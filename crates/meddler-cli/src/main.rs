@@ -2,6 +2,7 @@ use clap::{Parser, Subcommand};
 use tracing_subscriber::EnvFilter;
 
 mod agent_cmd;
+mod mcp_stdio_cmd;
 mod send_cmd;
 
 #[derive(Parser)]
@@ -51,16 +52,30 @@ enum Commands {
         /// Your agent name (defaults to "cli")
         #[arg(long, default_value = "cli")]
         from: String,
+
+        /// Seconds to wait for the reply
+        #[arg(long, default_value_t = 120)]
+        timeout: u64,
     },
 
     /// List all registered agents
     ListAgents,
+
+    /// Serve MCP over stdio for clients that can't speak HTTP, proxying to
+    /// the meddler server
+    McpStdio {
+        /// Orchestrator identity to act as (default: derived from the client)
+        #[arg(long, env = "MEDDLER_ORCHESTRATOR")]
+        orchestrator: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    // Logs go to stderr: stdout carries replies, and the MCP stream for mcp-stdio
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
         .init();
 
     let cli = Cli::parse();
@@ -74,9 +89,7 @@ async fn main() -> anyhow::Result<()> {
             mock,
         } => {
             let is_mock = mock
-                || std::env::var("AGENT_MODE")
-                    .map(|v| v.eq_ignore_ascii_case("mock"))
-                    .unwrap_or(false);
+                || std::env::var("AGENT_MODE").is_ok_and(|v| v.eq_ignore_ascii_case("mock"));
 
             let mode = match (is_mock, llm_url) {
                 (false, Some(url)) => agent_cmd::AgentMode::Llm {
@@ -91,8 +104,10 @@ async fn main() -> anyhow::Result<()> {
             agent,
             message,
             from,
+            timeout,
         } => {
-            send_cmd::run(&cli.meddler_url, &from, &agent, &message).await?;
+            let timeout = std::time::Duration::from_secs(timeout);
+            send_cmd::run(&cli.meddler_url, &from, &agent, &message, timeout).await?;
         }
         Commands::ListAgents => {
             let client = reqwest::Client::new();
@@ -118,6 +133,9 @@ async fn main() -> anyhow::Result<()> {
                 eprintln!("Error: {}", serde_json::to_string_pretty(error)?);
             }
         }
+        Commands::McpStdio { orchestrator } => {
+            mcp_stdio_cmd::run(&cli.meddler_url, orchestrator).await?;
+        }
     }
 
     Ok(())
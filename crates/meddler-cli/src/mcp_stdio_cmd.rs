@@ -0,0 +1,229 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, StatusCode};
+use reqwest_eventsource::{Event, EventSource};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_stream::StreamExt;
+
+use meddler_mcp::jsonrpc::INTERNAL_ERROR;
+use meddler_mcp::{JsonRpcResponse, ORCHESTRATOR_HEADER, PROTOCOL_VERSION_HEADER, SESSION_HEADER};
+
+/// Serve MCP over stdio by proxying every JSON-RPC line on stdin to the
+/// meddler server's Streamable HTTP endpoint, and writing its responses and
+/// notifications to stdout, one per line.
+///
+/// Requests are forwarded concurrently, so a client can cancel a blocking
+/// tool call while it runs. Once `initialize` opens a session, the session's
+/// stream is relayed too. The session ends when stdin closes.
+pub async fn run(meddler_url: &str, orchestrator: Option<String>) -> anyhow::Result<()> {
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = out_rx.recv().await {
+            stdout.write_all(line.as_bytes()).await?;
+            stdout.write_all(b"\n").await?;
+            stdout.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let proxy = Arc::new(Proxy {
+        client: Client::new(),
+        url: format!("{meddler_url}/mcp"),
+        orchestrator,
+        session: Mutex::new(None),
+        out: out_tx,
+    });
+    tracing::info!("Proxying MCP stdio to {}", proxy.url);
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut requests = JoinSet::new();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => {
+                    let proxy = proxy.clone();
+                    requests.spawn(async move { proxy.forward(line).await });
+                }
+                None => break,
+            },
+            Some(finished) = requests.join_next() => log_failure(finished),
+        }
+    }
+
+    // stdin closed: let outstanding requests answer, then end the session
+    while let Some(finished) = requests.join_next().await {
+        log_failure(finished);
+    }
+    proxy.close().await;
+    drop(proxy);
+
+    writer.await?
+}
+
+fn log_failure(finished: Result<anyhow::Result<()>, tokio::task::JoinError>) {
+    match finished {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!("Failed to proxy MCP message: {e}"),
+        Err(e) => tracing::error!("MCP proxy task failed: {e}"),
+    }
+}
+
+/// The server-side session opened by `initialize`.
+struct Session {
+    id: String,
+    protocol_version: Option<String>,
+    stream: JoinHandle<()>,
+}
+
+struct Proxy {
+    client: Client,
+    url: String,
+    orchestrator: Option<String>,
+    session: Mutex<Option<Session>>,
+    out: mpsc::UnboundedSender<String>,
+}
+
+impl Proxy {
+    /// Forward one JSON-RPC message and write out whatever the server answers.
+    async fn forward(&self, line: String) -> anyhow::Result<()> {
+        let id = serde_json::from_str::<Value>(&line)
+            .ok()
+            .and_then(|message| message.get("id").cloned())
+            .unwrap_or(Value::Null);
+
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json")
+            .body(line);
+        if let Some(orchestrator) = &self.orchestrator {
+            request = request.header(ORCHESTRATOR_HEADER, orchestrator);
+        }
+        if let Some(session) = self.lock_session().as_ref() {
+            request = request.header(SESSION_HEADER, &session.id);
+            if let Some(version) = &session.protocol_version {
+                request = request.header(PROTOCOL_VERSION_HEADER, version);
+            }
+        }
+
+        let resp = match request.send().await {
+            Ok(resp) => resp,
+            Err(e) => return self.fail(id, &format!("Cannot reach meddler: {e}")),
+        };
+        let status = resp.status();
+        let new_session = resp
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        if status == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+
+        let body = resp.text().await?;
+        let Ok(message) = serde_json::from_str::<Value>(&body) else {
+            return self.fail(id, &format!("meddler returned {status}: {body}"));
+        };
+        if let Some(session_id) = new_session {
+            let version = message["result"]["protocolVersion"].as_str();
+            self.start_session(session_id, version.map(str::to_string));
+        }
+        self.send(&message)
+    }
+
+    /// Remember the session `initialize` created and relay its stream.
+    fn start_session(&self, id: String, protocol_version: Option<String>) {
+        let stream = self.relay_stream(&id);
+        let previous = self.lock_session().replace(Session {
+            id,
+            protocol_version,
+            stream,
+        });
+        if let Some(previous) = previous {
+            previous.stream.abort();
+        }
+    }
+
+    /// Copy the session's server-initiated messages to stdout.
+    fn relay_stream(&self, session_id: &str) -> JoinHandle<()> {
+        let request = self
+            .client
+            .get(&self.url)
+            .header(ACCEPT, "text/event-stream")
+            .header(SESSION_HEADER, session_id);
+        let out = self.out.clone();
+
+        tokio::spawn(async move {
+            let mut es = match EventSource::new(request) {
+                Ok(es) => es,
+                Err(e) => {
+                    tracing::error!("Cannot open MCP session stream: {e}");
+                    return;
+                }
+            };
+            while let Some(event) = es.next().await {
+                match event {
+                    Ok(Event::Open) => tracing::info!("MCP session stream connected"),
+                    Ok(Event::Message(msg)) => {
+                        if out.send(msg.data).is_err() {
+                            break;
+                        }
+                    }
+                    Err(reqwest_eventsource::Error::InvalidStatusCode(status, _))
+                        if status == StatusCode::NOT_FOUND =>
+                    {
+                        tracing::warn!("MCP session ended");
+                        break;
+                    }
+                    Err(e) => tracing::warn!("MCP session stream error: {e}"),
+                }
+            }
+            es.close();
+        })
+    }
+
+    /// End the session, if one was opened.
+    async fn close(&self) {
+        let Some(session) = self.lock_session().take() else {
+            return;
+        };
+        session.stream.abort();
+        if let Err(e) = self
+            .client
+            .delete(&self.url)
+            .header(SESSION_HEADER, &session.id)
+            .send()
+            .await
+        {
+            tracing::warn!("Failed to end MCP session {}: {e}", session.id);
+        }
+    }
+
+    /// Answer a request the server couldn't, so the client isn't left
+    /// waiting. Failed notifications are only logged.
+    fn fail(&self, id: Value, message: &str) -> anyhow::Result<()> {
+        tracing::warn!("{message}");
+        if id.is_null() {
+            return Ok(());
+        }
+        let response = JsonRpcResponse::error(id, INTERNAL_ERROR, message);
+        self.send(&serde_json::to_value(response)?)
+    }
+
+    fn send(&self, message: &Value) -> anyhow::Result<()> {
+        self.out
+            .send(serde_json::to_string(message)?)
+            .map_err(|_| anyhow::anyhow!("stdout closed"))
+    }
+
+    fn lock_session(&self) -> std::sync::MutexGuard<'_, Option<Session>> {
+        self.session.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -1,11 +1,18 @@
+use std::time::Duration;
+
 use reqwest::Client;
+use reqwest_eventsource::{Event, EventSource};
+use tokio_stream::StreamExt;
+
+use crate::agent_cmd;
 
-/// Send a message to an agent and print the result.
+/// Send a message to an agent and print its reply.
 pub async fn run(
     meddler_url: &str,
     from: &str,
     to: &str,
     content: &str,
+    timeout: Duration,
 ) -> anyhow::Result<()> {
     let client = Client::new();
 
@@ -19,6 +26,14 @@ pub async fn run(
         .send()
         .await?;
 
+    // Listen on our own inbox before sending, so the reply can't slip past
+    let mut es = EventSource::get(format!("{meddler_url}/agent/sse/{from}"));
+    match tokio::time::timeout(timeout, es.next()).await {
+        Ok(Some(Ok(Event::Open))) => {}
+        Ok(Some(Err(e))) => anyhow::bail!("Failed to connect to inbox: {e}"),
+        _ => anyhow::bail!("Failed to connect to inbox"),
+    }
+
     // Send the message
     let resp = client
         .post(format!("{meddler_url}/agent/message"))
@@ -36,7 +51,37 @@ pub async fn run(
     }
 
     let result: serde_json::Value = resp.json().await?;
-    println!("{}", serde_json::to_string_pretty(&result)?);
+    let message_id = result["message_id"].clone();
+    tracing::info!("Sent message {message_id}");
+
+    // Wait for the recipient to answer
+    let reply = tokio::time::timeout(timeout, async {
+        while let Some(event) = es.next().await {
+            match event {
+                Ok(Event::Message(msg)) => {
+                    let Ok(message) = serde_json::from_str::<serde_json::Value>(&msg.data) else {
+                        continue;
+                    };
+                    // Skip anything else that was still queued for us
+                    if message["in_reply_to"] == message_id {
+                        return Some(message);
+                    }
+                }
+                Ok(Event::Open) => {}
+                Err(e) => tracing::warn!("SSE error: {e}"),
+            }
+        }
+        None
+    })
+    .await;
+    es.close();
+
+    let Ok(Some(reply)) = reply else {
+        anyhow::bail!("No reply from '{to}' within {}s", timeout.as_secs());
+    };
+
+    println!("{}", reply["content"].as_str().unwrap_or_default());
+    agent_cmd::acknowledge(&client, meddler_url, from, &reply).await?;
 
     Ok(())
 }
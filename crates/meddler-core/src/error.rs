@@ -1,4 +1,4 @@
-use crate::types::AgentId;
+use crate::types::{AgentId, MessageId, TaskId, TaskState};
 
 /// Core error type for the meddler system.
 #[derive(Debug, thiserror::Error)]
@@ -9,8 +9,28 @@ pub enum Error {
     #[error("agent not found by id: {0}")]
     AgentNotFoundById(AgentId),
 
+    #[error("message not found: {0}")]
+    MessageNotFound(MessageId),
+
     #[error("task not found: {0}")]
-    TaskNotFound(crate::types::TaskId),
+    TaskNotFound(TaskId),
+
+    #[error("task {task} cannot go from {from} to {to}")]
+    InvalidTaskTransition {
+        task: TaskId,
+        from: TaskState,
+        to: TaskState,
+    },
+
+    #[error("task {0} is {1} and accepts no new messages or subtasks")]
+    TaskClosed(TaskId, TaskState),
+
+    #[error("subtask budget of {requested}s exceeds the {available}s left on task {parent}")]
+    SubtaskBudgetExceeded {
+        parent: TaskId,
+        requested: i64,
+        available: i64,
+    },
 
     #[error("database error: {0}")]
     Database(String),
@@ -3,4 +3,7 @@ pub mod traits;
 pub mod types;
 
 pub use error::Error;
-pub use types::{Agent, AgentId, Message, MessageId, Task, TaskId, TaskStatus};
+pub use types::{
+    Agent, AgentId, DeliveryState, Message, MessageId, Task, TaskAssignment, TaskId, TaskRole,
+    TaskState, TaskStatus,
+};
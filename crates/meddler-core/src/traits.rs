@@ -1,9 +1,10 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use crate::error::Error;
 use crate::types::{
-    Agent, AgentId, CreateMessage, CreateTask, Message, MessageFilter, RegisterAgent, Task,
-    TaskId, TaskStatus,
+    Agent, AgentId, CreateMessage, CreateTask, Message, MessageFilter, MessageId, RegisterAgent,
+    Task, TaskAssignment, TaskId, TaskRole, TaskState, TaskStatus,
 };
 
 /// Registry for managing agent identities.
@@ -19,6 +20,9 @@ pub trait AgentRegistry: Send + Sync {
     /// Get an agent by ID.
     async fn get_by_id(&self, id: AgentId) -> Result<Agent, Error>;
 
+    /// Get several agents by ID in one go. Unknown IDs are skipped.
+    async fn get_by_ids(&self, ids: &[AgentId]) -> Result<Vec<Agent>, Error>;
+
     /// List all registered agents.
     async fn list(&self) -> Result<Vec<Agent>, Error>;
 
@@ -32,22 +36,105 @@ pub trait MessageStore: Send + Sync {
     /// Create and persist a new message.
     async fn create(&self, params: CreateMessage) -> Result<Message, Error>;
 
+    /// Get a message by ID.
+    async fn get_message(&self, id: MessageId) -> Result<Message, Error>;
+
     /// Query messages with optional filters.
     async fn query(&self, filter: MessageFilter) -> Result<Vec<Message>, Error>;
+
+    /// Count the messages matching a filter, ignoring its limit.
+    async fn count(&self, filter: MessageFilter) -> Result<u64, Error>;
+
+    /// The distinct senders and recipients of the messages under a task.
+    async fn task_participants(&self, task_id: TaskId) -> Result<Vec<AgentId>, Error>;
+
+    /// List the messages a recipient should be sent when it (re)connects,
+    /// ordered by `seq`: everything still pending, plus — when the client
+    /// resumes from a cursor — every message after `after_seq`.
+    async fn backlog_for(
+        &self,
+        recipient_id: AgentId,
+        after_seq: Option<i64>,
+    ) -> Result<Vec<Message>, Error>;
+
+    /// List the messages addressed to a recipient that it hasn't acknowledged
+    /// yet, pending or delivered, ordered by `seq`.
+    async fn unacknowledged_for(&self, recipient_id: AgentId) -> Result<Vec<Message>, Error>;
+
+    /// Mark a message as delivered to a live recipient stream. Messages that
+    /// are already delivered or acknowledged are left untouched.
+    async fn mark_delivered(&self, id: MessageId) -> Result<(), Error>;
+
+    /// Record that the recipient processed a message. Idempotent.
+    ///
+    /// Fails with [`Error::MessageNotFound`] if the message doesn't exist or
+    /// isn't addressed to `recipient_id`.
+    async fn acknowledge(&self, id: MessageId, recipient_id: AgentId) -> Result<Message, Error>;
+
+    /// Return delivered-but-unacknowledged messages to the pending queue if
+    /// they were delivered before `delivered_before` and have been attempted
+    /// fewer than `max_attempts` times. Returns the requeued messages.
+    async fn requeue_unacknowledged(
+        &self,
+        delivered_before: DateTime<Utc>,
+        max_attempts: i32,
+    ) -> Result<Vec<Message>, Error>;
 }
 
 /// Store for managing tasks.
 #[async_trait]
 pub trait TaskStore: Send + Sync {
-    /// Create a new task.
+    /// Create a new task. A subtask's budget is checked against, or defaults
+    /// to, its parent's remaining time (see [`TaskStatus::subtask_budget`]).
     async fn create(&self, params: CreateTask) -> Result<Task, Error>;
 
     /// Get a task by ID.
     async fn get(&self, id: TaskId) -> Result<Task, Error>;
 
+    /// List all tasks, newest first.
+    async fn list(&self) -> Result<Vec<Task>, Error>;
+
+    /// List the tasks currently in `state`, oldest first.
+    async fn list_in_state(&self, state: TaskState) -> Result<Vec<Task>, Error>;
+
+    /// List the direct subtasks of a task, oldest first.
+    async fn list_subtasks(&self, parent: TaskId) -> Result<Vec<Task>, Error>;
+
     /// Get the computed status of a task.
     async fn get_status(&self, id: TaskId) -> Result<TaskStatus, Error>;
 
-    /// Mark a task as started (sets `started_at` if not already set).
+    /// Mark a task as started ahead of a message being sent under it: sets
+    /// `started_at` if not already set and moves a pending task to running.
+    ///
+    /// Fails with [`Error::TaskClosed`] if the task has closed, since closed
+    /// tasks accept no new messages.
     async fn mark_started(&self, id: TaskId) -> Result<(), Error>;
+
+    /// Move a task to `state`, recording `outcome` and `completed_at` when it
+    /// closes.
+    ///
+    /// Fails with [`Error::InvalidTaskTransition`] unless
+    /// [`TaskState::can_transition_to`] allows the move.
+    async fn transition(
+        &self,
+        id: TaskId,
+        state: TaskState,
+        outcome: Option<String>,
+    ) -> Result<Task, Error>;
+
+    /// Assign an agent to a task, or change the role of one already assigned.
+    ///
+    /// Fails with [`Error::TaskClosed`] if the task has closed.
+    async fn assign(
+        &self,
+        task_id: TaskId,
+        agent_id: AgentId,
+        role: TaskRole,
+    ) -> Result<TaskAssignment, Error>;
+
+    /// Remove an agent from a task. Returns false if it wasn't assigned.
+    async fn unassign(&self, task_id: TaskId, agent_id: AgentId) -> Result<bool, Error>;
+
+    /// List the agents assigned to a task, in the order they were assigned.
+    async fn list_assignments(&self, task_id: TaskId) -> Result<Vec<TaskAssignment>, Error>;
 }
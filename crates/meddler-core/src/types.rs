@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::error::Error;
+
 /// Unique identifier for an agent.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -74,6 +76,10 @@ impl std::fmt::Display for TaskId {
     }
 }
 
+/// Name of the agent the server itself sends notices as, such as task budget
+/// warnings. Its messages are for information only and expect no reply.
+pub const SYSTEM_AGENT: &str = "__meddler__";
+
 /// A registered agent in the meddler system.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Agent {
@@ -84,25 +90,163 @@ pub struct Agent {
     pub last_seen_at: DateTime<Utc>,
 }
 
+/// Where a message is in its journey to the recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    /// Stored, but not yet pushed to a live recipient stream.
+    Pending,
+    /// Pushed to at least one live recipient stream.
+    Delivered,
+    /// The recipient confirmed it processed the message.
+    Acknowledged,
+}
+
+impl DeliveryState {
+    /// The canonical string form, as stored in the database.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Acknowledged => "acknowledged",
+        }
+    }
+}
+
+impl std::fmt::Display for DeliveryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DeliveryState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "delivered" => Ok(Self::Delivered),
+            "acknowledged" => Ok(Self::Acknowledged),
+            other => Err(format!("unknown delivery state: {other}")),
+        }
+    }
+}
+
 /// A point-to-point message between two agents.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: MessageId,
+    /// Monotonic position in the global message log, used as a stream cursor.
+    pub seq: i64,
     pub sender_id: AgentId,
     pub recipient_id: AgentId,
     pub task_id: Option<TaskId>,
+    /// The message this one answers, if it is a reply.
+    pub in_reply_to: Option<MessageId>,
     pub content: String,
+    pub delivery_state: DeliveryState,
+    /// How many times the message has been pushed to its recipient.
+    pub delivery_attempts: i32,
     pub created_at: DateTime<Utc>,
 }
 
+/// Where a task is in its lifecycle.
+///
+/// Tasks start out pending, run once the first message is sent under them,
+/// and end completed, failed, cancelled or timed out. Closed tasks never
+/// reopen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// Created, but no message has been sent under it yet.
+    Pending,
+    /// Work is under way.
+    Running,
+    /// Finished successfully.
+    Completed,
+    /// Finished without achieving its goal.
+    Failed,
+    /// Abandoned before it finished.
+    Cancelled,
+    /// Ran out of its time budget.
+    TimedOut,
+}
+
+impl TaskState {
+    /// The canonical string form, as stored in the database.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+            Self::TimedOut => "timed_out",
+        }
+    }
+
+    /// Whether the task has ended. Closed tasks accept no new messages.
+    #[must_use]
+    pub fn is_closed(self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Failed | Self::Cancelled | Self::TimedOut
+        )
+    }
+
+    /// Whether a task in this state may move to `next`: pending tasks start
+    /// running, and any open task may close. Only running tasks time out,
+    /// since the budget clock starts with the first message.
+    #[must_use]
+    pub fn can_transition_to(self, next: Self) -> bool {
+        match (self, next) {
+            (Self::Pending, Self::Running) | (Self::Running, Self::TimedOut) => true,
+            (Self::Pending, Self::TimedOut) => false,
+            (Self::Pending | Self::Running, next) => next.is_closed(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for TaskState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TaskState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "running" => Ok(Self::Running),
+            "completed" => Ok(Self::Completed),
+            "failed" => Ok(Self::Failed),
+            "cancelled" => Ok(Self::Cancelled),
+            "timed_out" => Ok(Self::TimedOut),
+            other => Err(format!("unknown task state: {other}")),
+        }
+    }
+}
+
 /// A task that groups related messages and tracks time budgets.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: TaskId,
     pub title: String,
     pub created_by: AgentId,
+    /// The task this one is a step of.
+    pub parent_task_id: Option<TaskId>,
     pub time_budget_secs: Option<i64>,
+    pub state: TaskState,
     pub started_at: Option<DateTime<Utc>>,
+    /// When the task closed.
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Summary of how the task ended, given when it closed.
+    pub outcome: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -115,9 +259,11 @@ pub struct TaskStatus {
 }
 
 impl TaskStatus {
-    /// Compute the status of a task at the given point in time.
+    /// Compute the status of a task at the given point in time. The clock
+    /// stops when the task closes.
     #[must_use]
     pub fn compute(task: Task, now: DateTime<Utc>) -> Self {
+        let now = task.completed_at.unwrap_or(now);
         let elapsed_secs = task
             .started_at
             .map(|started| (now - started).num_seconds());
@@ -133,6 +279,84 @@ impl TaskStatus {
             remaining_secs,
         }
     }
+
+    /// The budget a new subtask of this task gets: what was requested, or
+    /// the time this task has left if nothing was. A subtask can't outlast
+    /// its parent, so a request for more than that is refused, as is a
+    /// subtask of a closed task.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::TaskClosed`] or [`Error::SubtaskBudgetExceeded`].
+    pub fn subtask_budget(&self, requested: Option<i64>) -> Result<Option<i64>, Error> {
+        if self.task.state.is_closed() {
+            return Err(Error::TaskClosed(self.task.id, self.task.state));
+        }
+        let available = self.remaining_secs.or(self.task.time_budget_secs);
+        match (requested, available) {
+            (Some(requested), Some(available)) if requested > available => {
+                Err(Error::SubtaskBudgetExceeded {
+                    parent: self.task.id,
+                    requested,
+                    available,
+                })
+            }
+            (Some(requested), _) => Ok(Some(requested)),
+            (None, available) => Ok(available),
+        }
+    }
+}
+
+/// The part an agent plays in a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskRole {
+    /// Responsible for the task's outcome.
+    Owner,
+    /// Does part of the work.
+    Contributor,
+    /// Checks the work of others.
+    Reviewer,
+}
+
+impl TaskRole {
+    /// The canonical string form, as stored in the database.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Contributor => "contributor",
+            Self::Reviewer => "reviewer",
+        }
+    }
+}
+
+impl std::fmt::Display for TaskRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TaskRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner" => Ok(Self::Owner),
+            "contributor" => Ok(Self::Contributor),
+            "reviewer" => Ok(Self::Reviewer),
+            other => Err(format!("unknown task role: {other}")),
+        }
+    }
+}
+
+/// An agent assigned to work on a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAssignment {
+    pub task_id: TaskId,
+    pub agent_id: AgentId,
+    pub role: TaskRole,
+    pub assigned_at: DateTime<Utc>,
 }
 
 /// Parameters for creating a new message.
@@ -141,6 +365,7 @@ pub struct CreateMessage {
     pub sender_id: AgentId,
     pub recipient_id: AgentId,
     pub task_id: Option<TaskId>,
+    pub in_reply_to: Option<MessageId>,
     pub content: String,
 }
 
@@ -149,6 +374,8 @@ pub struct CreateMessage {
 pub struct CreateTask {
     pub title: String,
     pub created_by: AgentId,
+    pub parent_task_id: Option<TaskId>,
+    /// Defaults to the parent's remaining time for subtasks.
     pub time_budget_secs: Option<i64>,
 }
 
@@ -160,11 +387,42 @@ pub struct RegisterAgent {
 }
 
 /// Filters for querying messages.
+///
+/// `after_seq` and `before_seq` are exclusive `seq` cursors: to page through
+/// results, pass the `seq` of the last message of one page as the cursor for
+/// the next (`after_seq` oldest first, `before_seq` newest first).
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MessageFilter {
     pub task_id: Option<TaskId>,
     pub sender_id: Option<AgentId>,
     pub recipient_id: Option<AgentId>,
+    pub in_reply_to: Option<MessageId>,
+    /// Only messages exchanged between these two agents, in either direction.
+    pub between: Option<(AgentId, AgentId)>,
+    /// Only messages created at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only messages created before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Full-text search over message content. Supports web search syntax:
+    /// `"quoted phrases"`, `or`, and `-excluded` words.
+    pub text: Option<String>,
+    /// Only messages with a `seq` greater than this.
+    pub after_seq: Option<i64>,
+    /// Only messages with a `seq` less than this.
+    pub before_seq: Option<i64>,
+    /// Return at most this many messages.
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub order: MessageOrder,
+}
+
+/// The order messages are returned in, by `seq`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageOrder {
+    #[default]
+    OldestFirst,
+    NewestFirst,
 }
 
 #[cfg(test)]
@@ -202,8 +460,12 @@ mod tests {
             id: TaskId::new(),
             title: "Test".to_string(),
             created_by: AgentId::new(),
+            parent_task_id: None,
             time_budget_secs: Some(3600),
+            state: TaskState::Pending,
             started_at: None,
+            completed_at: None,
+            outcome: None,
             created_at: now,
         };
 
@@ -220,8 +482,12 @@ mod tests {
             id: TaskId::new(),
             title: "Test".to_string(),
             created_by: AgentId::new(),
+            parent_task_id: None,
             time_budget_secs: Some(3600), // 1 hour
+            state: TaskState::Running,
             started_at: Some(started),
+            completed_at: None,
+            outcome: None,
             created_at: now,
         };
 
@@ -238,8 +504,12 @@ mod tests {
             id: TaskId::new(),
             title: "Test".to_string(),
             created_by: AgentId::new(),
+            parent_task_id: None,
             time_budget_secs: Some(3600), // 1 hour budget
+            state: TaskState::Running,
             started_at: Some(started),
+            completed_at: None,
+            outcome: None,
             created_at: now,
         };
 
@@ -256,8 +526,12 @@ mod tests {
             id: TaskId::new(),
             title: "Test".to_string(),
             created_by: AgentId::new(),
+            parent_task_id: None,
             time_budget_secs: None,
+            state: TaskState::Running,
             started_at: Some(started),
+            completed_at: None,
+            outcome: None,
             created_at: now,
         };
 
@@ -266,6 +540,115 @@ mod tests {
         assert!(status.remaining_secs.is_none()); // No budget = no remaining
     }
 
+    #[test]
+    fn task_status_stops_when_closed() {
+        let now = chrono::Utc::now();
+        let started = now - chrono::Duration::seconds(1800);
+        let task = Task {
+            id: TaskId::new(),
+            title: "Test".to_string(),
+            created_by: AgentId::new(),
+            parent_task_id: None,
+            time_budget_secs: Some(3600),
+            state: TaskState::Completed,
+            started_at: Some(started),
+            completed_at: Some(started + chrono::Duration::seconds(600)),
+            outcome: Some("Done".to_string()),
+            created_at: now,
+        };
+
+        let status = TaskStatus::compute(task, now);
+        assert_eq!(status.elapsed_secs, Some(600));
+        assert_eq!(status.remaining_secs, Some(3000));
+    }
+
+    #[test]
+    fn subtask_budget_is_bounded_by_parent() {
+        let now = chrono::Utc::now();
+        let mut parent = Task {
+            id: TaskId::new(),
+            title: "Parent".to_string(),
+            created_by: AgentId::new(),
+            parent_task_id: None,
+            time_budget_secs: Some(3600),
+            state: TaskState::Pending,
+            started_at: None,
+            completed_at: None,
+            outcome: None,
+            created_at: now,
+        };
+
+        // Not started: the whole budget is available
+        let status = TaskStatus::compute(parent.clone(), now);
+        assert_eq!(status.subtask_budget(None).unwrap(), Some(3600));
+
+        parent.state = TaskState::Running;
+        parent.started_at = Some(now - chrono::Duration::seconds(600));
+        let status = TaskStatus::compute(parent.clone(), now);
+        assert_eq!(status.subtask_budget(None).unwrap(), Some(3000));
+        assert_eq!(status.subtask_budget(Some(1200)).unwrap(), Some(1200));
+        assert!(matches!(
+            status.subtask_budget(Some(3001)),
+            Err(Error::SubtaskBudgetExceeded { available: 3000, .. })
+        ));
+
+        parent.time_budget_secs = None;
+        let status = TaskStatus::compute(parent.clone(), now);
+        assert_eq!(status.subtask_budget(None).unwrap(), None);
+        assert_eq!(status.subtask_budget(Some(99_999)).unwrap(), Some(99_999));
+
+        parent.state = TaskState::Completed;
+        let status = TaskStatus::compute(parent, now);
+        assert!(matches!(status.subtask_budget(None), Err(Error::TaskClosed(..))));
+    }
+
+    #[test]
+    fn task_state_transitions() {
+        use TaskState::{Cancelled, Completed, Failed, Pending, Running, TimedOut};
+
+        assert!(Pending.can_transition_to(Running));
+        assert!(Pending.can_transition_to(Cancelled));
+        assert!(Running.can_transition_to(Completed));
+        assert!(Running.can_transition_to(Failed));
+        assert!(Running.can_transition_to(Cancelled));
+        assert!(Running.can_transition_to(TimedOut));
+        assert!(!Pending.can_transition_to(TimedOut));
+
+        assert!(!Running.can_transition_to(Pending));
+        assert!(!Running.can_transition_to(Running));
+        for closed in [Completed, Failed, Cancelled, TimedOut] {
+            assert!(closed.is_closed());
+            for next in [Pending, Running, Completed, Failed, Cancelled, TimedOut] {
+                assert!(!closed.can_transition_to(next));
+            }
+        }
+    }
+
+    #[test]
+    fn task_role_roundtrip() {
+        for role in [TaskRole::Owner, TaskRole::Contributor, TaskRole::Reviewer] {
+            assert_eq!(role.as_str().parse::<TaskRole>(), Ok(role));
+            assert_eq!(serde_json::to_value(role).unwrap(), role.as_str());
+        }
+        assert!("lead".parse::<TaskRole>().is_err());
+    }
+
+    #[test]
+    fn task_state_roundtrip() {
+        for state in [
+            TaskState::Pending,
+            TaskState::Running,
+            TaskState::Completed,
+            TaskState::Failed,
+            TaskState::Cancelled,
+            TaskState::TimedOut,
+        ] {
+            assert_eq!(state.as_str().parse::<TaskState>(), Ok(state));
+            assert_eq!(serde_json::to_value(state).unwrap(), state.as_str());
+        }
+        assert!("done".parse::<TaskState>().is_err());
+    }
+
     #[test]
     fn agent_serialization() {
         let agent = Agent {
@@ -286,17 +669,38 @@ mod tests {
     fn message_serialization() {
         let msg = Message {
             id: MessageId::new(),
+            seq: 42,
             sender_id: AgentId::new(),
             recipient_id: AgentId::new(),
             task_id: Some(TaskId::new()),
+            in_reply_to: Some(MessageId::new()),
             content: "Hello world".to_string(),
+            delivery_state: DeliveryState::Pending,
+            delivery_attempts: 0,
             created_at: chrono::Utc::now(),
         };
 
         let json = serde_json::to_string(&msg).unwrap();
         let deserialized: Message = serde_json::from_str(&json).unwrap();
         assert_eq!(msg.id, deserialized.id);
+        assert_eq!(msg.seq, deserialized.seq);
         assert_eq!(msg.content, deserialized.content);
         assert_eq!(msg.task_id, deserialized.task_id);
+        assert_eq!(msg.in_reply_to, deserialized.in_reply_to);
+        assert_eq!(msg.delivery_state, deserialized.delivery_state);
+    }
+
+    #[test]
+    fn delivery_state_roundtrip() {
+        for state in [
+            DeliveryState::Pending,
+            DeliveryState::Delivered,
+            DeliveryState::Acknowledged,
+        ] {
+            assert_eq!(state.as_str().parse::<DeliveryState>(), Ok(state));
+            let json = serde_json::to_string(&state).unwrap();
+            assert_eq!(json, format!("\"{state}\""));
+        }
+        assert!("lost".parse::<DeliveryState>().is_err());
     }
 }
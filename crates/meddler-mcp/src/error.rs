@@ -0,0 +1,169 @@
+use serde_json::Value;
+
+/// Why a tool call failed.
+///
+/// Unknown tools and invalid arguments are protocol errors, reported to the
+/// client as JSON-RPC errors. Everything else happens while the tool runs and
+/// is returned as an `isError` result, so the model can read it and recover.
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    #[error("unknown tool: {0}")]
+    UnknownTool(String),
+
+    #[error("invalid arguments: {0}")]
+    InvalidArguments(String),
+
+    #[error("agent not found: {0}")]
+    AgentNotFound(String),
+
+    #[error("task not found: {0}")]
+    TaskNotFound(String),
+
+    #[error("message not found: {0}")]
+    MessageNotFound(String),
+
+    /// The task's state doesn't allow the call, e.g. a message to a closed
+    /// task or a subtask asking for more time than its parent has left.
+    #[error("{0}")]
+    InvalidTaskState(String),
+
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+impl ToolError {
+    /// Whether the call was malformed rather than failing while it ran.
+    #[must_use]
+    pub fn is_protocol_error(&self) -> bool {
+        matches!(self, Self::UnknownTool(_) | Self::InvalidArguments(_))
+    }
+
+    /// Stable machine-readable name of the error.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::UnknownTool(_) => "unknown_tool",
+            Self::InvalidArguments(_) => "invalid_arguments",
+            Self::AgentNotFound(_) => "agent_not_found",
+            Self::TaskNotFound(_) => "task_not_found",
+            Self::MessageNotFound(_) => "message_not_found",
+            Self::InvalidTaskState(_) => "invalid_task_state",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    /// The `tools/call` result reporting this error to the model.
+    #[must_use]
+    pub fn to_result(&self) -> Value {
+        let explanation = serde_json::json!({
+            "error": self.kind(),
+            "message": self.to_string(),
+        });
+        serde_json::json!({
+            "content": [{
+                "type": "text",
+                "text": serde_json::to_string_pretty(&explanation).unwrap_or_default()
+            }],
+            "isError": true
+        })
+    }
+}
+
+impl From<meddler_core::error::Error> for ToolError {
+    fn from(e: meddler_core::error::Error) -> Self {
+        use meddler_core::error::Error;
+        match e {
+            Error::AgentNotFound(name) => Self::AgentNotFound(name),
+            Error::AgentNotFoundById(id) => Self::AgentNotFound(id.to_string()),
+            Error::TaskNotFound(id) => Self::TaskNotFound(id.to_string()),
+            Error::MessageNotFound(id) => Self::MessageNotFound(id.to_string()),
+            Error::InvalidTaskTransition { .. }
+            | Error::TaskClosed(..)
+            | Error::SubtaskBudgetExceeded { .. } => Self::InvalidTaskState(e.to_string()),
+            other => Self::Internal(other.to_string()),
+        }
+    }
+}
+
+/// Why a prompt couldn't be rendered. Everything but [`PromptError::Internal`]
+/// is a problem with the request, reported as `INVALID_PARAMS`.
+#[derive(Debug, thiserror::Error)]
+pub enum PromptError {
+    #[error("unknown prompt: {0}")]
+    UnknownPrompt(String),
+
+    #[error("missing required argument: {0}")]
+    MissingArgument(String),
+
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+impl From<meddler_core::error::Error> for PromptError {
+    fn from(e: meddler_core::error::Error) -> Self {
+        use meddler_core::error::Error;
+        match e {
+            Error::AgentNotFound(_) | Error::AgentNotFoundById(_) | Error::TaskNotFound(_) => {
+                Self::InvalidArgument(e.to_string())
+            }
+            other => Self::Internal(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_errors() {
+        assert!(ToolError::UnknownTool("x".into()).is_protocol_error());
+        assert!(ToolError::InvalidArguments("x".into()).is_protocol_error());
+        assert!(!ToolError::AgentNotFound("x".into()).is_protocol_error());
+        assert!(!ToolError::Internal("x".into()).is_protocol_error());
+    }
+
+    #[test]
+    fn execution_error_result() {
+        let result = ToolError::AgentNotFound("researcher".into()).to_result();
+        assert_eq!(result["isError"], true);
+
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let explanation: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(explanation["error"], "agent_not_found");
+        assert_eq!(explanation["message"], "agent not found: researcher");
+    }
+
+    #[test]
+    fn from_core_error() {
+        let e = ToolError::from(meddler_core::error::Error::AgentNotFound("bob".into()));
+        assert!(matches!(e, ToolError::AgentNotFound(name) if name == "bob"));
+
+        let task = meddler_core::types::TaskId::new();
+        let closed = meddler_core::types::TaskState::Completed;
+        let e = ToolError::from(meddler_core::error::Error::TaskClosed(task, closed));
+        assert_eq!(e.kind(), "invalid_task_state");
+        assert_eq!(
+            e.to_string(),
+            format!("task {task} is completed and accepts no new messages or subtasks")
+        );
+
+        let e = ToolError::from(meddler_core::error::Error::SubtaskBudgetExceeded {
+            parent: task,
+            requested: 7200,
+            available: 3600,
+        });
+        assert!(!e.is_protocol_error());
+        assert_eq!(e.kind(), "invalid_task_state");
+
+        let message = meddler_core::types::MessageId::new();
+        let e = ToolError::from(meddler_core::error::Error::MessageNotFound(message));
+        assert_eq!(e.kind(), "message_not_found");
+
+        let e = ToolError::from(meddler_core::error::Error::Database("down".into()));
+        assert!(matches!(e, ToolError::Internal(_)));
+    }
+}
@@ -50,6 +50,139 @@ impl JsonRpcResponse {
             }),
         }
     }
+
+    /// Create an error response carrying `data` with details about the error.
+    pub fn error_with_data(id: Value, code: i32, message: impl Into<String>, data: Value) -> Self {
+        let mut response = Self::error(id, code, message);
+        if let Some(error) = &mut response.error {
+            error.data = Some(data);
+        }
+        response
+    }
+}
+
+impl JsonRpcRequest {
+    /// Validate a parsed JSON value as a request object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `INVALID_REQUEST` response, addressed to the request's `id`
+    /// when it has a usable one, describing what was wrong.
+    #[allow(clippy::result_large_err)]
+    pub fn from_value(value: Value) -> Result<Self, JsonRpcResponse> {
+        let Value::Object(object) = &value else {
+            return Err(invalid_request(
+                Value::Null,
+                format!("expected a request object, found {}", type_name(&value)),
+            ));
+        };
+
+        let id = match object.get("id") {
+            None => Value::Null,
+            Some(id @ (Value::Null | Value::String(_) | Value::Number(_))) => id.clone(),
+            Some(other) => {
+                return Err(invalid_request(
+                    Value::Null,
+                    format!("'id' must be a string, number or null, found {}", type_name(other)),
+                ));
+            }
+        };
+
+        match object.get("jsonrpc") {
+            Some(Value::String(version)) if version == "2.0" => {}
+            Some(other) => {
+                return Err(invalid_request(
+                    id,
+                    format!("'jsonrpc' must be \"2.0\", found {other}"),
+                ));
+            }
+            None => return Err(invalid_request(id, "missing 'jsonrpc' member")),
+        }
+
+        match object.get("method") {
+            Some(Value::String(_)) => {}
+            Some(other) => {
+                return Err(invalid_request(
+                    id,
+                    format!("'method' must be a string, found {}", type_name(other)),
+                ));
+            }
+            None => return Err(invalid_request(id, "missing 'method' member")),
+        }
+
+        if let Some(params) = object.get("params") {
+            if !matches!(params, Value::Object(_) | Value::Array(_) | Value::Null) {
+                return Err(invalid_request(
+                    id,
+                    format!("'params' must be an object or array, found {}", type_name(params)),
+                ));
+            }
+        }
+
+        serde_json::from_value(value).map_err(|e| invalid_request(id, e.to_string()))
+    }
+}
+
+/// The body of a JSON-RPC 2.0 request message: one request, or a batch of
+/// them sent as an array. Batch entries are validated one by one, so a
+/// malformed entry only fails itself.
+#[derive(Debug, Clone)]
+pub enum JsonRpcRequestBody {
+    Single(JsonRpcRequest),
+    Batch(Vec<Result<JsonRpcRequest, JsonRpcResponse>>),
+}
+
+impl JsonRpcRequestBody {
+    /// Parse and validate a raw message body.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error response for a body that is rejected as a whole:
+    /// `PARSE_ERROR` for invalid JSON, `INVALID_REQUEST` for anything that is
+    /// not a valid request or a non-empty batch.
+    #[allow(clippy::result_large_err)]
+    pub fn parse(bytes: &[u8]) -> Result<Self, JsonRpcResponse> {
+        let value: Value = serde_json::from_slice(bytes).map_err(|e| {
+            JsonRpcResponse::error_with_data(
+                Value::Null,
+                PARSE_ERROR,
+                "Parse error",
+                serde_json::json!({
+                    "reason": e.to_string(),
+                    "line": e.line(),
+                    "column": e.column(),
+                }),
+            )
+        })?;
+
+        match value {
+            Value::Array(entries) if entries.is_empty() => {
+                Err(invalid_request(Value::Null, "empty batch"))
+            }
+            Value::Array(entries) => Ok(Self::Batch(
+                entries.into_iter().map(JsonRpcRequest::from_value).collect(),
+            )),
+            value => JsonRpcRequest::from_value(value).map(Self::Single),
+        }
+    }
+}
+
+/// The body of a JSON-RPC 2.0 response message, mirroring the request's shape.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcResponseBody {
+    Single(JsonRpcResponse),
+    Batch(Vec<JsonRpcResponse>),
+}
+
+impl JsonRpcResponseBody {
+    /// Collect the responses to a batch. Notifications get no entry, and a
+    /// batch made up only of notifications gets no response at all.
+    #[must_use]
+    pub fn batch(responses: impl IntoIterator<Item = Option<JsonRpcResponse>>) -> Option<Self> {
+        let responses: Vec<_> = responses.into_iter().flatten().collect();
+        (!responses.is_empty()).then_some(Self::Batch(responses))
+    }
 }
 
 /// A JSON-RPC 2.0 error object.
@@ -61,6 +194,26 @@ pub struct JsonRpcError {
     pub data: Option<Value>,
 }
 
+fn invalid_request(id: Value, reason: impl Into<String>) -> JsonRpcResponse {
+    JsonRpcResponse::error_with_data(
+        id,
+        INVALID_REQUEST,
+        "Invalid Request",
+        serde_json::json!({ "reason": reason.into() }),
+    )
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
 /// Standard JSON-RPC error codes.
 pub const PARSE_ERROR: i32 = -32700;
 pub const INVALID_REQUEST: i32 = -32600;
@@ -68,6 +221,9 @@ pub const METHOD_NOT_FOUND: i32 = -32601;
 pub const INVALID_PARAMS: i32 = -32602;
 pub const INTERNAL_ERROR: i32 = -32603;
 
+/// MCP error code for a `resources/read` of a resource that doesn't exist.
+pub const RESOURCE_NOT_FOUND: i32 = -32002;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -134,4 +290,82 @@ mod tests {
         assert_eq!(params["name"], "send_message");
         assert_eq!(params["arguments"]["to"], "researcher");
     }
+
+    #[test]
+    fn parse_batch_request() {
+        let json = r#"[
+            {"jsonrpc": "2.0", "id": 1, "method": "tools/list"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"}
+        ]"#;
+        let body = JsonRpcRequestBody::parse(json.as_bytes()).unwrap();
+        let JsonRpcRequestBody::Batch(batch) = body else {
+            panic!("expected a batch");
+        };
+        assert_eq!(batch.len(), 2);
+        assert!(batch[1].as_ref().unwrap().id.is_null());
+
+        let single =
+            JsonRpcRequestBody::parse(br#"{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}"#)
+                .unwrap();
+        assert!(matches!(single, JsonRpcRequestBody::Single(_)));
+    }
+
+    #[test]
+    fn batch_response_omits_notifications() {
+        let body = JsonRpcResponseBody::batch([
+            Some(JsonRpcResponse::success(serde_json::json!(1), serde_json::json!({}))),
+            None,
+        ])
+        .unwrap();
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["id"], 1);
+
+        assert!(JsonRpcResponseBody::batch([None, None]).is_none());
+    }
+
+    #[test]
+    fn parse_error_reports_position() {
+        let err = JsonRpcRequestBody::parse(b"{\"jsonrpc\": ").unwrap_err();
+        let error = err.error.unwrap();
+        assert_eq!(error.code, PARSE_ERROR);
+        assert!(err.id.is_null());
+        assert_eq!(error.data.unwrap()["line"], 1);
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let cases: [&[u8]; 5] = [
+            b"[]",
+            br#""tools/list""#,
+            br#"{"jsonrpc": "1.0", "id": 1, "method": "tools/list"}"#,
+            br#"{"jsonrpc": "2.0", "id": 1}"#,
+            br#"{"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": 5}"#,
+        ];
+        for body in cases {
+            let err = JsonRpcRequestBody::parse(body).unwrap_err();
+            let error = err.error.unwrap();
+            assert_eq!(error.code, INVALID_REQUEST);
+            assert!(error.data.unwrap()["reason"].is_string());
+        }
+
+        // The id is kept when it can be read, so the client can match the error
+        let err = JsonRpcRequestBody::parse(br#"{"jsonrpc": "1.0", "id": 7, "method": "x"}"#)
+            .unwrap_err();
+        assert_eq!(err.id, serde_json::json!(7));
+    }
+
+    #[test]
+    fn invalid_batch_entry_fails_alone() {
+        let body = JsonRpcRequestBody::parse(
+            br#"[{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}, 42]"#,
+        )
+        .unwrap();
+        let JsonRpcRequestBody::Batch(batch) = body else {
+            panic!("expected a batch");
+        };
+        assert!(batch[0].is_ok());
+        let err = batch[1].as_ref().unwrap_err();
+        assert_eq!(err.error.as_ref().unwrap().code, INVALID_REQUEST);
+    }
 }
@@ -1,5 +1,17 @@
+pub mod error;
 pub mod jsonrpc;
+pub mod prompts;
+pub mod protocol;
+pub mod resources;
 pub mod tools;
 
-pub use jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
-pub use tools::{ToolDefinition, ToolRegistry};
+pub use error::{PromptError, ToolError};
+pub use jsonrpc::{
+    JsonRpcError, JsonRpcRequest, JsonRpcRequestBody, JsonRpcResponse, JsonRpcResponseBody,
+};
+pub use prompts::{GetPromptResult, Prompt, PromptArgument, PromptContent, PromptMessage, Role};
+pub use protocol::{
+    ProtocolVersion, ORCHESTRATOR_HEADER, PROTOCOL_VERSION_HEADER, SESSION_HEADER,
+};
+pub use resources::{Resource, ResourceContents, ResourceTemplate, ResourceUri};
+pub use tools::{Tool, ToolAnnotations, ToolDefinition, ToolRegistry};
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::resources::ResourceContents;
+
+/// A prompt template an MCP client can offer its user, for `prompts/list`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prompt {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<PromptArgument>,
+}
+
+/// An argument the user fills in before a prompt is rendered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Who a prompt message is spoken by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// Content of a prompt message: plain text, or a resource embedded inline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PromptContent {
+    Text { text: String },
+    Resource { resource: ResourceContents },
+}
+
+/// One message of a rendered prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptMessage {
+    pub role: Role,
+    pub content: PromptContent,
+}
+
+impl PromptMessage {
+    /// A plain-text message from the user.
+    #[must_use]
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: PromptContent::Text { text: text.into() },
+        }
+    }
+}
+
+/// A rendered prompt, as returned by `prompts/get`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPromptResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub messages: Vec<PromptMessage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_serialize_with_content_type() {
+        let result = GetPromptResult {
+            description: None,
+            messages: vec![
+                PromptMessage::user("hello"),
+                PromptMessage {
+                    role: Role::User,
+                    content: PromptContent::Resource {
+                        resource: ResourceContents {
+                            uri: "meddler://agents/researcher".to_string(),
+                            mime_type: "application/json".to_string(),
+                            text: "{}".to_string(),
+                        },
+                    },
+                },
+            ],
+        };
+        let json = serde_json::to_value(result).unwrap();
+        assert!(json.get("description").is_none());
+        assert_eq!(
+            json["messages"][0],
+            serde_json::json!({"role": "user", "content": {"type": "text", "text": "hello"}})
+        );
+        assert_eq!(json["messages"][1]["content"]["type"], "resource");
+        assert_eq!(
+            json["messages"][1]["content"]["resource"]["mimeType"],
+            "application/json"
+        );
+    }
+
+    #[test]
+    fn prompts_omit_empty_arguments() {
+        let prompt = Prompt {
+            name: "ping".to_string(),
+            description: None,
+            arguments: Vec::new(),
+        };
+        assert_eq!(
+            serde_json::to_value(prompt).unwrap(),
+            serde_json::json!({"name": "ping"})
+        );
+    }
+}
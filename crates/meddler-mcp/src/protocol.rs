@@ -0,0 +1,144 @@
+/// HTTP header carrying the Streamable HTTP session id.
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// HTTP header carrying the protocol revision of a Streamable HTTP request.
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// HTTP header a client can set to pick a stable orchestrator identity.
+pub const ORCHESTRATOR_HEADER: &str = "x-meddler-orchestrator";
+
+/// MCP protocol revisions this server speaks, oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    V2024_11_05,
+    V2025_03_26,
+    V2025_06_18,
+}
+
+impl ProtocolVersion {
+    /// Every supported revision, oldest first.
+    pub const ALL: [Self; 3] = [Self::V2024_11_05, Self::V2025_03_26, Self::V2025_06_18];
+
+    /// The newest revision, offered to clients asking for one we don't know.
+    pub const LATEST: Self = Self::V2025_06_18;
+
+    /// The revision to assume for an HTTP request that doesn't say which one it
+    /// speaks, as the spec prescribes.
+    pub const HTTP_DEFAULT: Self = Self::V2025_03_26;
+
+    /// The revision's date string, as used on the wire.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::V2024_11_05 => "2024-11-05",
+            Self::V2025_03_26 => "2025-03-26",
+            Self::V2025_06_18 => "2025-06-18",
+        }
+    }
+
+    /// Pick the revision to answer a client's `initialize` with: the one it
+    /// asked for if we support it, otherwise the latest we do.
+    #[must_use]
+    pub fn negotiate(requested: Option<&str>) -> Self {
+        requested
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(Self::LATEST)
+    }
+
+    /// Tool definitions may carry `annotations` (read-only, destructive, ...).
+    #[must_use]
+    pub fn supports_tool_annotations(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Tools may declare an `outputSchema` and return `structuredContent`.
+    #[must_use]
+    pub fn supports_structured_output(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
+    /// Progress notifications may carry a human-readable `message`.
+    #[must_use]
+    pub fn supports_progress_message(self) -> bool {
+        self >= Self::V2025_03_26
+    }
+
+    /// Requests may be sent as JSON-RPC batches. Added in 2025-03-26 and
+    /// removed again in 2025-06-18.
+    #[must_use]
+    pub fn supports_batch(self) -> bool {
+        self == Self::V2025_03_26
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ProtocolVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|v| v.as_str() == s)
+            .ok_or_else(|| format!("unsupported protocol version: {s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for version in ProtocolVersion::ALL {
+            assert_eq!(version.as_str().parse::<ProtocolVersion>(), Ok(version));
+        }
+        assert!("2023-01-01".parse::<ProtocolVersion>().is_err());
+    }
+
+    #[test]
+    fn negotiate_echoes_supported_version() {
+        assert_eq!(
+            ProtocolVersion::negotiate(Some("2024-11-05")),
+            ProtocolVersion::V2024_11_05
+        );
+        assert_eq!(
+            ProtocolVersion::negotiate(Some("2025-03-26")),
+            ProtocolVersion::V2025_03_26
+        );
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_latest() {
+        assert_eq!(
+            ProtocolVersion::negotiate(Some("1999-01-01")),
+            ProtocolVersion::LATEST
+        );
+        assert_eq!(ProtocolVersion::negotiate(None), ProtocolVersion::LATEST);
+    }
+
+    #[test]
+    fn feature_gates() {
+        let old = ProtocolVersion::V2024_11_05;
+        assert!(!old.supports_tool_annotations());
+        assert!(!old.supports_structured_output());
+        assert!(!old.supports_progress_message());
+        assert!(!old.supports_batch());
+
+        let mid = ProtocolVersion::V2025_03_26;
+        assert!(mid.supports_tool_annotations());
+        assert!(!mid.supports_structured_output());
+        assert!(mid.supports_progress_message());
+        assert!(mid.supports_batch());
+
+        let new = ProtocolVersion::V2025_06_18;
+        assert!(new.supports_tool_annotations());
+        assert!(new.supports_structured_output());
+        assert!(new.supports_progress_message());
+        assert!(!new.supports_batch());
+    }
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+
+use meddler_core::types::TaskId;
+
+/// A resource an MCP client can list and read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// A parameterised family of resources, e.g. every task's transcript.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    pub description: String,
+    pub mime_type: String,
+}
+
+/// The text contents of a resource, as returned by `resources/read`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    pub mime_type: String,
+    pub text: String,
+}
+
+/// A `meddler://` resource address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceUri {
+    /// `meddler://agents/{name}`: an agent's profile.
+    Agent(String),
+    /// `meddler://agents/{name}/inbox`: messages waiting for an orchestrator.
+    AgentInbox(String),
+    /// `meddler://tasks/{id}`: a task and its time status.
+    Task(TaskId),
+    /// `meddler://tasks/{id}/transcript`: every message sent under a task.
+    TaskTranscript(TaskId),
+}
+
+impl ResourceUri {
+    pub const SCHEME: &'static str = "meddler://";
+
+    /// MIME type of the resource's contents.
+    #[must_use]
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Agent(_) | Self::AgentInbox(_) | Self::Task(_) => "application/json",
+            Self::TaskTranscript(_) => "text/plain",
+        }
+    }
+
+    /// Templates describing every kind of resource, for
+    /// `resources/templates/list`.
+    #[must_use]
+    pub fn templates() -> Vec<ResourceTemplate> {
+        vec![
+            ResourceTemplate {
+                uri_template: "meddler://agents/{name}".to_string(),
+                name: "Agent".to_string(),
+                description: "An agent's description and connection status.".to_string(),
+                mime_type: "application/json".to_string(),
+            },
+            ResourceTemplate {
+                uri_template: "meddler://agents/{name}/inbox".to_string(),
+                name: "Orchestrator inbox".to_string(),
+                description: "Messages an orchestrator hasn't read yet.".to_string(),
+                mime_type: "application/json".to_string(),
+            },
+            ResourceTemplate {
+                uri_template: "meddler://tasks/{id}".to_string(),
+                name: "Task".to_string(),
+                description: "A task with its elapsed and remaining time.".to_string(),
+                mime_type: "application/json".to_string(),
+            },
+            ResourceTemplate {
+                uri_template: "meddler://tasks/{id}/transcript".to_string(),
+                name: "Task transcript".to_string(),
+                description: "Every message exchanged under a task, oldest first.".to_string(),
+                mime_type: "text/plain".to_string(),
+            },
+        ]
+    }
+}
+
+impl std::fmt::Display for ResourceUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Agent(name) => write!(f, "{}agents/{name}", Self::SCHEME),
+            Self::AgentInbox(name) => write!(f, "{}agents/{name}/inbox", Self::SCHEME),
+            Self::Task(id) => write!(f, "{}tasks/{id}", Self::SCHEME),
+            Self::TaskTranscript(id) => write!(f, "{}tasks/{id}/transcript", Self::SCHEME),
+        }
+    }
+}
+
+impl std::str::FromStr for ResourceUri {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = s
+            .strip_prefix(Self::SCHEME)
+            .ok_or_else(|| format!("not a meddler resource URI: {s}"))?;
+
+        if let Some(rest) = path.strip_prefix("agents/") {
+            let (name, inbox) = match rest.strip_suffix("/inbox") {
+                Some(name) => (name, true),
+                None => (rest, false),
+            };
+            if name.is_empty() {
+                return Err(format!("missing agent name: {s}"));
+            }
+            return Ok(if inbox {
+                Self::AgentInbox(name.to_string())
+            } else {
+                Self::Agent(name.to_string())
+            });
+        }
+
+        if let Some(rest) = path.strip_prefix("tasks/") {
+            let (id, transcript) = match rest.strip_suffix("/transcript") {
+                Some(id) => (id, true),
+                None => (rest, false),
+            };
+            let id = id
+                .parse::<uuid::Uuid>()
+                .map(TaskId)
+                .map_err(|e| format!("invalid task id in {s}: {e}"))?;
+            return Ok(if transcript {
+                Self::TaskTranscript(id)
+            } else {
+                Self::Task(id)
+            });
+        }
+
+        Err(format!("unknown meddler resource: {s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_roundtrip() {
+        let id = TaskId::new();
+        for uri in [
+            ResourceUri::Agent("researcher".to_string()),
+            ResourceUri::AgentInbox("__orchestrator__:cursor".to_string()),
+            ResourceUri::Task(id),
+            ResourceUri::TaskTranscript(id),
+        ] {
+            assert_eq!(uri.to_string().parse::<ResourceUri>(), Ok(uri));
+        }
+    }
+
+    #[test]
+    fn parse_uris() {
+        let id = TaskId::new();
+        assert_eq!(
+            "meddler://agents/researcher".parse(),
+            Ok(ResourceUri::Agent("researcher".to_string()))
+        );
+        assert_eq!(
+            "meddler://agents/researcher/inbox".parse(),
+            Ok(ResourceUri::AgentInbox("researcher".to_string()))
+        );
+        assert_eq!(
+            format!("meddler://tasks/{id}/transcript").parse(),
+            Ok(ResourceUri::TaskTranscript(id))
+        );
+    }
+
+    #[test]
+    fn reject_invalid_uris() {
+        for uri in [
+            "https://example.com",
+            "meddler://agents/",
+            "meddler://agents//inbox",
+            "meddler://tasks/not-a-uuid",
+            "meddler://tasks/",
+            "meddler://widgets/1",
+        ] {
+            assert!(uri.parse::<ResourceUri>().is_err(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn templates_serialize_camel_case() {
+        let json = serde_json::to_value(ResourceUri::templates()).unwrap();
+        assert_eq!(json[3]["uriTemplate"], "meddler://tasks/{id}/transcript");
+        assert_eq!(json[3]["mimeType"], "text/plain");
+    }
+}
@@ -1,6 +1,13 @@
+use async_trait::async_trait;
+use schemars::generate::Contract;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::error::ToolError;
+use crate::protocol::ProtocolVersion;
+
 /// Definition of an MCP tool exposed to the orchestrator.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolDefinition {
@@ -8,102 +15,264 @@ pub struct ToolDefinition {
     pub description: String,
     #[serde(rename = "inputSchema")]
     pub input_schema: Value,
+    #[serde(rename = "outputSchema", skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+impl ToolDefinition {
+    /// Drop the fields a client on `protocol` doesn't know about.
+    #[must_use]
+    pub fn for_protocol(mut self, protocol: ProtocolVersion) -> Self {
+        if !protocol.supports_tool_annotations() {
+            self.annotations = None;
+        }
+        if !protocol.supports_structured_output() {
+            self.output_schema = None;
+        }
+        self
+    }
+}
+
+/// Hints about a tool's behaviour that clients use to decide, e.g., whether
+/// a call needs the user's approval. Unset hints take the spec's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    /// The tool doesn't modify anything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// The tool may delete or overwrite data, rather than only add to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Calling the tool again with the same arguments has no further effect.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// The tool reaches outside the server, e.g. the open web.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+impl ToolAnnotations {
+    /// Hints for a tool that only reads.
+    #[must_use]
+    pub fn read_only() -> Self {
+        Self {
+            read_only_hint: Some(true),
+            ..Self::default()
+        }
+    }
+
+    /// Hints for a tool that adds something new (e.g. sends a message) each
+    /// time it's called, without touching what exists.
+    #[must_use]
+    pub fn additive() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(false),
+            idempotent_hint: Some(false),
+            open_world_hint: None,
+        }
+    }
+
+    /// Hints for a tool that updates something that exists, where calling it
+    /// again changes nothing further (e.g. acknowledges messages).
+    #[must_use]
+    pub fn idempotent() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(false),
+            idempotent_hint: Some(true),
+            open_world_hint: None,
+        }
+    }
+
+    /// Hints for a tool that changes something that exists in a way that
+    /// can't be undone (e.g. closes a task).
+    #[must_use]
+    pub fn destructive() -> Self {
+        Self {
+            read_only_hint: Some(false),
+            destructive_hint: Some(true),
+            idempotent_hint: Some(false),
+            open_world_hint: None,
+        }
+    }
 }
 
-/// Registry of all MCP tools available to the orchestrator.
-pub struct ToolRegistry;
+/// An MCP tool with typed arguments.
+///
+/// `C` is the context the host passes to every call, e.g. the server state and
+/// the calling session. The tool's input schema is generated from `Args`, and
+/// incoming arguments are deserialized into it before [`Tool::call`] runs, so
+/// the advertised schema and the parsing can't drift apart.
+#[async_trait]
+pub trait Tool<C>: Send + Sync + 'static {
+    /// The tool's arguments. Field doc comments become the schema's
+    /// descriptions.
+    type Args: DeserializeOwned + JsonSchema + Send;
+
+    /// Name the tool is listed and called by.
+    fn name(&self) -> &'static str;
+
+    /// What the tool does, for the model deciding whether to call it.
+    fn description(&self) -> &'static str;
+
+    /// Behaviour hints for the client, if the tool declares any.
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        None
+    }
+
+    /// Schema of the tool's result, for tools whose result has a fixed
+    /// shape. See [`output_schema`].
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+
+    /// Run the tool.
+    async fn call(&self, ctx: &C, args: Self::Args) -> Result<Value, ToolError>;
+}
+
+/// Object-safe form of [`Tool`], taking and validating raw JSON arguments.
+#[async_trait]
+trait DynTool<C>: Send + Sync {
+    fn tool_name(&self) -> &'static str;
+
+    fn definition(&self) -> ToolDefinition;
+
+    async fn call_json(&self, ctx: &C, args: Value) -> Result<Value, ToolError>;
+}
+
+#[async_trait]
+impl<C, T> DynTool<C> for T
+where
+    C: Sync,
+    T: Tool<C>,
+{
+    fn tool_name(&self) -> &'static str {
+        self.name()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            input_schema: input_schema::<T::Args>(),
+            output_schema: self.output_schema(),
+            annotations: self.annotations(),
+        }
+    }
+
+    async fn call_json(&self, ctx: &C, args: Value) -> Result<Value, ToolError> {
+        let args = serde_json::from_value(args)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        self.call(ctx, args).await
+    }
+}
+
+/// The JSON schema MCP clients are given for a tool's arguments.
+#[must_use]
+pub fn input_schema<T: JsonSchema>() -> Value {
+    let mut schema = schema_for::<T>(Contract::Deserialize);
+    strip_null_types(&mut schema);
+    schema
+}
+
+/// The JSON schema of a tool result of type `T`, for [`Tool::output_schema`].
+///
+/// Unlike arguments, results keep their `null` alternatives: an empty
+/// optional field is serialized as `null` unless it is skipped.
+#[must_use]
+pub fn output_schema<T: JsonSchema>() -> Value {
+    schema_for::<T>(Contract::Serialize)
+}
+
+fn schema_for<T: JsonSchema>(contract: Contract) -> Value {
+    let mut schema = schemars::generate::SchemaSettings::draft2020_12()
+        .with(|s| {
+            s.meta_schema = None;
+            s.inline_subschemas = true;
+            s.contract = contract;
+        })
+        .into_generator()
+        .into_root_schema_for::<T>()
+        .to_value();
+    if let Value::Object(object) = &mut schema {
+        object.remove("title");
+        // Clients expect every tool to take an object, even without arguments
+        object
+            .entry("properties")
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+    }
+    schema
+}
+
+/// Optional arguments are simply left out, so drop the `null` alternative
+/// schemars adds to their types; some clients choke on type unions.
+fn strip_null_types(schema: &mut Value) {
+    match schema {
+        Value::Object(object) => {
+            if let Some(Value::Array(types)) = object.get_mut("type") {
+                types.retain(|t| t != "null");
+                if let [single] = types.as_slice() {
+                    let single = single.clone();
+                    object.insert("type".to_string(), single);
+                }
+            }
+            object.values_mut().for_each(strip_null_types);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_null_types),
+        _ => {}
+    }
+}
+
+/// Registry of the MCP tools available to the orchestrator. Lists their
+/// definitions for `tools/list` and dispatches `tools/call` to them.
+pub struct ToolRegistry<C> {
+    tools: Vec<Box<dyn DynTool<C>>>,
+}
+
+impl<C: Sync + 'static> ToolRegistry<C> {
+    /// An empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { tools: Vec::new() }
+    }
+
+    /// Add a tool, replacing any registered under the same name.
+    pub fn register<T: Tool<C>>(&mut self, tool: T) -> &mut Self {
+        self.tools.retain(|t| t.tool_name() != tool.name());
+        self.tools.push(Box::new(tool));
+        self
+    }
 
-impl ToolRegistry {
     /// Return the list of tool definitions for the MCP `tools/list` method.
     #[must_use]
-    pub fn definitions() -> Vec<ToolDefinition> {
-        vec![
-            ToolDefinition {
-                name: "list_agents".to_string(),
-                description: "List all registered agents and their descriptions.".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }),
-            },
-            ToolDefinition {
-                name: "send_message".to_string(),
-                description: "Send a message to a specific agent by name. Returns the message ID. The response will arrive via SSE notification.".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "to": {
-                            "type": "string",
-                            "description": "Name of the recipient agent"
-                        },
-                        "content": {
-                            "type": "string",
-                            "description": "Message content to send"
-                        },
-                        "task_id": {
-                            "type": "string",
-                            "description": "Optional task ID to group related messages"
-                        }
-                    },
-                    "required": ["to", "content"]
-                }),
-            },
-            ToolDefinition {
-                name: "get_messages".to_string(),
-                description: "Retrieve message history with optional filters.".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "task_id": {
-                            "type": "string",
-                            "description": "Filter by task ID"
-                        },
-                        "sender": {
-                            "type": "string",
-                            "description": "Filter by sender agent name"
-                        },
-                        "recipient": {
-                            "type": "string",
-                            "description": "Filter by recipient agent name"
-                        }
-                    },
-                    "required": []
-                }),
-            },
-            ToolDefinition {
-                name: "create_task".to_string(),
-                description: "Create a new task to group related messages. Optionally set a time budget in seconds.".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "title": {
-                            "type": "string",
-                            "description": "Title of the task"
-                        },
-                        "time_budget_secs": {
-                            "type": "integer",
-                            "description": "Optional time budget in seconds (e.g., 28800 for 8 hours)"
-                        }
-                    },
-                    "required": ["title"]
-                }),
-            },
-            ToolDefinition {
-                name: "get_task_status".to_string(),
-                description: "Get the status of a task, including elapsed and remaining time.".to_string(),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "task_id": {
-                            "type": "string",
-                            "description": "The task ID to check"
-                        }
-                    },
-                    "required": ["task_id"]
-                }),
-            },
-        ]
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.iter().map(|t| t.definition()).collect()
+    }
+
+    /// Call a tool by name with raw JSON arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::UnknownTool`] if no such tool is registered,
+    /// [`ToolError::InvalidArguments`] if the arguments don't match its schema,
+    /// and otherwise whatever the tool itself fails with.
+    pub async fn call(&self, ctx: &C, name: &str, args: Value) -> Result<Value, ToolError> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|t| t.tool_name() == name)
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?;
+        tool.call_json(ctx, args).await
+    }
+}
+
+impl<C: Sync + 'static> Default for ToolRegistry<C> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -111,35 +280,180 @@ impl ToolRegistry {
 mod tests {
     use super::*;
 
+    /// Test context counting how often tools ran.
+    #[derive(Default)]
+    struct Ctx {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct EchoArgs {
+        /// Text to echo back
+        text: String,
+        /// How often to repeat it
+        times: Option<usize>,
+    }
+
+    struct Echo;
+
+    #[async_trait]
+    impl Tool<Ctx> for Echo {
+        type Args = EchoArgs;
+
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn description(&self) -> &'static str {
+            "Echo the text back."
+        }
+
+        async fn call(&self, ctx: &Ctx, args: EchoArgs) -> Result<Value, ToolError> {
+            ctx.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(serde_json::json!(args.text.repeat(args.times.unwrap_or(1))))
+        }
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct NoArgs {}
+
+    #[derive(Serialize, JsonSchema)]
+    struct Pong {
+        /// Always "pong"
+        reply: String,
+        /// Never measured
+        latency_ms: Option<u64>,
+    }
+
+    struct Ping;
+
+    #[async_trait]
+    impl Tool<Ctx> for Ping {
+        type Args = NoArgs;
+
+        fn name(&self) -> &'static str {
+            "ping"
+        }
+
+        fn description(&self) -> &'static str {
+            "Pong."
+        }
+
+        fn annotations(&self) -> Option<ToolAnnotations> {
+            Some(ToolAnnotations::read_only())
+        }
+
+        fn output_schema(&self) -> Option<Value> {
+            Some(output_schema::<Pong>())
+        }
+
+        async fn call(&self, _ctx: &Ctx, _args: NoArgs) -> Result<Value, ToolError> {
+            Ok(serde_json::to_value(Pong {
+                reply: "pong".to_string(),
+                latency_ms: None,
+            })
+            .unwrap())
+        }
+    }
+
+    fn registry() -> ToolRegistry<Ctx> {
+        let mut registry = ToolRegistry::new();
+        registry.register(Echo).register(Ping);
+        registry
+    }
+
     #[test]
-    fn all_tools_defined() {
-        let tools = ToolRegistry::definitions();
+    fn definitions_are_generated() {
+        let tools = registry().definitions();
         let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["echo", "ping"]);
+
+        let echo = &tools[0];
+        assert_eq!(echo.input_schema["type"], "object");
+        assert_eq!(echo.input_schema["required"], serde_json::json!(["text"]));
+        assert_eq!(
+            echo.input_schema["properties"]["text"]["description"],
+            "Text to echo back"
+        );
+        assert_eq!(echo.input_schema["properties"]["times"]["type"], "integer");
+        assert!(echo.input_schema.get("title").is_none());
 
-        assert!(names.contains(&"list_agents"));
-        assert!(names.contains(&"send_message"));
-        assert!(names.contains(&"get_messages"));
-        assert!(names.contains(&"create_task"));
-        assert!(names.contains(&"get_task_status"));
-        assert_eq!(tools.len(), 5);
+        assert_eq!(tools[1].input_schema["properties"], serde_json::json!({}));
     }
 
     #[test]
     fn tools_serialize() {
-        let tools = ToolRegistry::definitions();
-        let json = serde_json::to_string(&tools).unwrap();
-        assert!(json.contains("list_agents"));
+        let json = serde_json::to_string(&registry().definitions()).unwrap();
+        assert!(json.contains("echo"));
         assert!(json.contains("inputSchema"));
     }
 
     #[test]
-    fn send_message_has_required_params() {
-        let tools = ToolRegistry::definitions();
-        let send = tools.iter().find(|t| t.name == "send_message").unwrap();
-        let required = send.input_schema["required"].as_array().unwrap();
-
-        let required_names: Vec<&str> = required.iter().filter_map(Value::as_str).collect();
-        assert!(required_names.contains(&"to"));
-        assert!(required_names.contains(&"content"));
+    fn annotations_and_output_schema() {
+        let tools = registry().definitions();
+        assert!(tools[0].annotations.is_none());
+        assert!(tools[0].output_schema.is_none());
+
+        let ping = serde_json::to_value(&tools[1]).unwrap();
+        assert_eq!(ping["annotations"], serde_json::json!({"readOnlyHint": true}));
+        assert_eq!(
+            ping["outputSchema"]["required"],
+            serde_json::json!(["reply", "latency_ms"])
+        );
+        // Results serialize empty options as null, so their schema allows it
+        assert_eq!(
+            ping["outputSchema"]["properties"]["latency_ms"]["type"],
+            serde_json::json!(["integer", "null"])
+        );
+
+        let echo = serde_json::to_value(&tools[0]).unwrap();
+        assert!(echo.get("annotations").is_none());
+        assert!(echo.get("outputSchema").is_none());
+    }
+
+    #[test]
+    fn definitions_are_downgraded_for_older_protocols() {
+        let ping = registry().definitions().remove(1);
+
+        let mid = ping.clone().for_protocol(ProtocolVersion::V2025_03_26);
+        assert!(mid.annotations.is_some());
+        assert!(mid.output_schema.is_none());
+
+        let old = ping.for_protocol(ProtocolVersion::V2024_11_05);
+        assert!(old.annotations.is_none());
+        assert!(old.output_schema.is_none());
+    }
+
+    #[test]
+    fn register_replaces_same_name() {
+        let mut registry = registry();
+        registry.register(Echo);
+        assert_eq!(registry.definitions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn call_dispatches_typed_args() {
+        let ctx = Ctx::default();
+        let result = registry()
+            .call(&ctx, "echo", serde_json::json!({"text": "ab", "times": 2}))
+            .await
+            .unwrap();
+        assert_eq!(result, "abab");
+        assert_eq!(ctx.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn call_rejects_unknown_tool_and_bad_args() {
+        let ctx = Ctx::default();
+        let registry = registry();
+
+        let err = registry.call(&ctx, "nope", serde_json::json!({})).await;
+        assert!(matches!(err, Err(ToolError::UnknownTool(_))));
+
+        let err = registry
+            .call(&ctx, "echo", serde_json::json!({"times": 2}))
+            .await;
+        assert!(matches!(err, Err(ToolError::InvalidArguments(_))));
+        assert_eq!(ctx.calls.load(std::sync::atomic::Ordering::SeqCst), 0);
     }
 }
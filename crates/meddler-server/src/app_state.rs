@@ -1,8 +1,11 @@
 use std::sync::Arc;
 
 use meddler_core::traits::{AgentRegistry, MessageStore, TaskStore};
+use meddler_mcp::ToolRegistry;
 
+use crate::mcp_session::McpSessionManager;
 use crate::session::SessionManager;
+use crate::tools::ToolContext;
 
 /// Shared application state with injected dependencies.
 #[derive(Clone)]
@@ -11,4 +14,7 @@ pub struct AppState {
     pub message_store: Arc<dyn MessageStore>,
     pub task_store: Arc<dyn TaskStore>,
     pub sessions: Arc<SessionManager>,
+    pub mcp_sessions: Arc<McpSessionManager>,
+    /// Tools offered to MCP orchestrators.
+    pub tools: Arc<ToolRegistry<ToolContext>>,
 }
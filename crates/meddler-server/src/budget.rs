@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::task::JoinHandle;
+
+use meddler_core::error::Error;
+use meddler_core::types::{
+    Agent, AgentId, CreateMessage, RegisterAgent, Task, TaskId, TaskState, SYSTEM_AGENT,
+};
+
+use crate::app_state::AppState;
+use crate::delivery;
+use crate::mcp_session;
+use crate::redelivery::env_or;
+
+/// Settings for enforcing task time budgets.
+#[derive(Debug, Clone)]
+pub struct BudgetConfig {
+    /// How often running tasks are checked.
+    pub check_interval: Duration,
+    /// Percentages of the budget at which participants are warned.
+    pub warn_at_percent: Vec<u8>,
+    /// Close tasks as timed out when their budget runs out, so they refuse
+    /// new messages. Otherwise they are only announced as expired.
+    pub auto_timeout: bool,
+}
+
+impl BudgetConfig {
+    /// Read the config from `MEDDLER_BUDGET_CHECK_SECS` (default 10),
+    /// `MEDDLER_BUDGET_WARN_PERCENT` (comma-separated, default 80) and
+    /// `MEDDLER_BUDGET_AUTO_TIMEOUT` (default true). A check interval of 0
+    /// disables budget enforcement.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let check_secs = env_or("MEDDLER_BUDGET_CHECK_SECS", 10);
+        if check_secs == 0 {
+            return None;
+        }
+        let warn_at_percent = std::env::var("MEDDLER_BUDGET_WARN_PERCENT").map_or_else(
+            |_| vec![80],
+            |v| v.split(',').filter_map(|p| p.trim().parse().ok()).collect(),
+        );
+        Some(Self {
+            check_interval: Duration::from_secs(check_secs),
+            warn_at_percent,
+            auto_timeout: env_or("MEDDLER_BUDGET_AUTO_TIMEOUT", true),
+        })
+    }
+}
+
+/// Something a sweep announced about a task's budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetEvent {
+    /// The task has used at least this percentage of its budget.
+    Warning(u8),
+    /// The budget ran out.
+    Expired,
+}
+
+/// Watches the budgets of running tasks and announces each warning threshold
+/// and expiry once: to the task's orchestrator as an MCP log notification, and
+/// to it and every agent assigned to or taking part in the task as a message
+/// under it.
+///
+/// What was announced is remembered in memory only, so a restart may repeat
+/// the latest warning for a task.
+pub struct BudgetWatcher {
+    config: BudgetConfig,
+    /// Highest warning threshold announced per task.
+    warned: HashMap<TaskId, u8>,
+    /// Expiries announced for tasks left running (no auto timeout).
+    expired: HashSet<TaskId>,
+}
+
+impl BudgetWatcher {
+    #[must_use]
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config,
+            warned: HashMap::new(),
+            expired: HashSet::new(),
+        }
+    }
+
+    /// Check every running task with a budget as of `now` and announce what
+    /// changed since the last sweep. Returns the events announced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if running tasks cannot be loaded. Failures to
+    /// announce a single task are logged and retried on the next sweep.
+    pub async fn sweep(
+        &mut self,
+        state: &AppState,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(TaskId, BudgetEvent)>, Error> {
+        let running = state.task_store.list_in_state(TaskState::Running).await?;
+
+        // Forget tasks that have closed
+        let ids: HashSet<TaskId> = running.iter().map(|task| task.id).collect();
+        self.warned.retain(|id, _| ids.contains(id));
+        self.expired.retain(|id| ids.contains(id));
+
+        let mut events = Vec::new();
+        for task in running {
+            let Some(event) = self.next_event(&task, now) else {
+                continue;
+            };
+            match announce(state, &self.config, &task, event).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    tracing::warn!("Failed to announce budget of task {}: {e}", task.id);
+                    continue;
+                }
+            }
+            match event {
+                BudgetEvent::Warning(percent) => {
+                    self.warned.insert(task.id, percent);
+                }
+                BudgetEvent::Expired => {
+                    self.expired.insert(task.id);
+                }
+            }
+            events.push((task.id, event));
+        }
+        Ok(events)
+    }
+
+    /// The event a task is due, if it hasn't been announced yet. Of several
+    /// thresholds crossed at once, only the highest is announced.
+    fn next_event(&self, task: &Task, now: DateTime<Utc>) -> Option<BudgetEvent> {
+        let (Some(budget), Some(started)) = (task.time_budget_secs, task.started_at) else {
+            return None;
+        };
+        let elapsed = (now - started).num_seconds();
+
+        if elapsed >= budget {
+            return (!self.expired.contains(&task.id)).then_some(BudgetEvent::Expired);
+        }
+        let used = elapsed.saturating_mul(100) / budget;
+        let threshold = self
+            .config
+            .warn_at_percent
+            .iter()
+            .copied()
+            .filter(|&percent| used >= i64::from(percent))
+            .max()?;
+        let announced = self.warned.get(&task.id).copied();
+        (announced < Some(threshold)).then_some(BudgetEvent::Warning(threshold))
+    }
+}
+
+/// Spawn the background loop that enforces task budgets.
+pub fn spawn(state: AppState, config: BudgetConfig) -> JoinHandle<()> {
+    let period = config.check_interval;
+    let mut watcher = BudgetWatcher::new(config);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            match watcher.sweep(&state, Utc::now()).await {
+                Ok(events) if events.is_empty() => {}
+                Ok(events) => tracing::info!("Announced {} task budget events", events.len()),
+                Err(e) => tracing::error!("Budget sweep failed: {e}"),
+            }
+        }
+    })
+}
+
+/// Tell the task's orchestrator and participants about a budget event,
+/// closing the task first if it expired and timeouts are enforced. Returns
+/// false if the task closed in the meantime, so there was nothing to announce.
+///
+/// Participants hear it from the [`SYSTEM_AGENT`] rather than the
+/// orchestrator, so they can tell it needs no reply; a reply under a task
+/// that just timed out would be refused anyway.
+async fn announce(
+    state: &AppState,
+    config: &BudgetConfig,
+    task: &Task,
+    event: BudgetEvent,
+) -> Result<bool, Error> {
+    let budget = task.time_budget_secs.unwrap_or_default();
+    let timed_out = event == BudgetEvent::Expired && config.auto_timeout;
+    if timed_out {
+        let outcome = format!("Ran out of its {budget}s time budget");
+        match state
+            .task_store
+            .transition(task.id, TaskState::TimedOut, Some(outcome))
+            .await
+        {
+            Ok(_) => {}
+            Err(Error::InvalidTaskTransition { .. }) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let text = match event {
+        BudgetEvent::Warning(percent) => format!(
+            "Task '{}' has used {percent}% of its {budget}s time budget.",
+            task.title
+        ),
+        BudgetEvent::Expired if timed_out => format!(
+            "Task '{}' ran out of its {budget}s time budget and has timed out; \
+             it accepts no new messages.",
+            task.title
+        ),
+        BudgetEvent::Expired => format!(
+            "Task '{}' ran out of its {budget}s time budget.",
+            task.title
+        ),
+    };
+
+    let orchestrator = state.agent_registry.get_by_id(task.created_by).await?;
+    state
+        .mcp_sessions
+        .notify_orchestrator(
+            &orchestrator.name,
+            "notifications/message",
+            serde_json::json!({
+                "level": "warning",
+                "logger": "meddler",
+                "data": {
+                    "event": match event {
+                        BudgetEvent::Warning(_) => "budget_warning",
+                        BudgetEvent::Expired => "budget_expired",
+                    },
+                    "task_id": task.id,
+                    "message": text,
+                },
+            }),
+        )
+        .await;
+
+    let system = state
+        .agent_registry
+        .register(RegisterAgent {
+            name: SYSTEM_AGENT.to_string(),
+            description: "Notices from the meddler server".to_string(),
+        })
+        .await?;
+    // The orchestrator also gets it in its inbox, in case no stream was open
+    // to hear the notification
+    let recipients = std::iter::once(orchestrator).chain(participants(state, task).await?);
+    for recipient in recipients {
+        let message = state
+            .message_store
+            .create(CreateMessage {
+                sender_id: system.id,
+                recipient_id: recipient.id,
+                task_id: Some(task.id),
+                in_reply_to: None,
+                content: text.clone(),
+            })
+            .await?;
+        delivery::dispatch(state, &recipient.name, message).await;
+    }
+    Ok(true)
+}
+
+/// The worker agents assigned to a task or that sent or received messages
+/// under it.
+async fn participants(state: &AppState, task: &Task) -> Result<Vec<Agent>, Error> {
+    let assignments = state.task_store.list_assignments(task.id).await?;
+    let senders_and_recipients = state.message_store.task_participants(task.id).await?;
+
+    let mut seen = HashSet::new();
+    let ids: Vec<AgentId> = assignments
+        .iter()
+        .map(|a| a.agent_id)
+        .chain(senders_and_recipients)
+        .filter(|id| *id != task.created_by && seen.insert(*id))
+        .collect();
+
+    let mut participants = state.agent_registry.get_by_ids(&ids).await?;
+    participants.retain(|agent| mcp_session::is_worker(&agent.name));
+    Ok(participants)
+}
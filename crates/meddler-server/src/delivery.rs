@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::http::HeaderMap;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+
+use meddler_core::error::Error;
+use meddler_core::traits::{AgentRegistry, MessageStore};
+use meddler_core::types::{Agent, AgentId, Message, MessageId};
+use meddler_mcp::ResourceUri;
+
+use crate::app_state::AppState;
+use crate::mcp_session;
+
+/// How many messages an inbox buffers ahead of a slow SSE writer.
+const INBOX_BUFFER: usize = 64;
+
+/// A message as written to a recipient's stream, with the sender's name
+/// resolved so the recipient knows whom to reply to.
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    #[serde(flatten)]
+    pub message: Message,
+    pub sender_name: String,
+}
+
+/// Announce a freshly stored message: tell MCP sessions subscribed to its
+/// task's transcript, or to the inbox of the orchestrator it is addressed to,
+/// that it changed, then [`deliver`] it.
+pub async fn dispatch(state: &AppState, recipient_name: &str, message: Message) -> bool {
+    if let Some(task_id) = message.task_id {
+        state
+            .mcp_sessions
+            .resource_updated(&ResourceUri::TaskTranscript(task_id))
+            .await;
+    }
+    if mcp_session::is_orchestrator(recipient_name) {
+        state
+            .mcp_sessions
+            .resource_updated(&ResourceUri::AgentInbox(recipient_name.to_string()))
+            .await;
+    }
+    deliver(state, recipient_name, message).await
+}
+
+/// Push a message to its recipient's live stream, and to any in-process
+/// waiter blocked on the message it replies to.
+///
+/// Returns true if a listener received it, in which case the message is marked
+/// delivered. Otherwise it stays pending until the recipient opens its inbox.
+pub async fn deliver(state: &AppState, recipient_name: &str, message: Message) -> bool {
+    let id = message.id;
+    state.sessions.resolve_reply(&message).await;
+    let delivered = state.sessions.notify(recipient_name, message).await;
+    if delivered {
+        if let Err(e) = state.message_store.mark_delivered(id).await {
+            tracing::warn!("Failed to mark message {id} as delivered: {e}");
+        }
+    }
+    delivered
+}
+
+/// Read the SSE `Last-Event-ID` header sent by a reconnecting client.
+///
+/// Agent inbox event ids are message `seq` cursors, MCP session event ids
+/// count the session's notifications. Anything unparseable is ignored and the
+/// client is treated as connecting fresh.
+pub fn last_event_id(headers: &HeaderMap) -> Option<i64> {
+    headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// Open the inbox of a connecting agent.
+///
+/// The returned stream first yields the backlog — every message that queued up
+/// while the agent was offline, plus everything after `after_seq` when resuming
+/// — in `seq` order, and then switches over to live traffic. If the live
+/// channel lags, the skipped messages are replayed from the store.
+///
+/// # Errors
+///
+/// Returns an error if the backlog cannot be loaded.
+pub async fn open_inbox(
+    state: &AppState,
+    agent: &Agent,
+    after_seq: Option<i64>,
+) -> Result<ReceiverStream<Delivery>, Error> {
+    // Subscribe before reading the backlog so nothing sent in between is lost.
+    // Messages that show up in both are filtered out below.
+    let mut live = state.sessions.subscribe(&agent.name).await;
+    let backlog = state.message_store.backlog_for(agent.id, after_seq).await?;
+
+    let (tx, rx) = mpsc::channel(INBOX_BUFFER);
+    let mut pump = InboxPump {
+        store: state.message_store.clone(),
+        registry: state.agent_registry.clone(),
+        sender_names: HashMap::new(),
+        agent: agent.clone(),
+        tx,
+        sent: HashMap::new(),
+        last_seq: after_seq,
+    };
+
+    tokio::spawn(async move {
+        if pump.send_backlog(backlog).await.is_err() {
+            return;
+        }
+
+        loop {
+            tokio::select! {
+                () = pump.tx.closed() => return,
+                received = live.recv() => match received {
+                    Ok(message) => {
+                        if pump.already_sent(&message) {
+                            continue;
+                        }
+                        if pump.send(message).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "Inbox for '{}' lagged by {skipped} messages, replaying from store",
+                            pump.agent.name
+                        );
+                        let replay = match pump.store.backlog_for(pump.agent.id, pump.last_seq).await {
+                            Ok(replay) => replay,
+                            Err(e) => {
+                                tracing::error!("Failed to replay inbox for '{}': {e}", pump.agent.name);
+                                return;
+                            }
+                        };
+                        if pump.send_backlog(replay).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+            }
+        }
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+/// Forwards one agent's messages into its SSE stream.
+struct InboxPump {
+    store: Arc<dyn MessageStore>,
+    registry: Arc<dyn AgentRegistry>,
+    sender_names: HashMap<AgentId, String>,
+    agent: Agent,
+    tx: mpsc::Sender<Delivery>,
+    /// Messages sent from the store that may still arrive on the live
+    /// channel, with the delivery attempt they were sent as. A redelivery is a
+    /// later attempt, so it is never mistaken for one of these.
+    sent: HashMap<MessageId, i32>,
+    last_seq: Option<i64>,
+}
+
+impl InboxPump {
+    async fn send(&mut self, message: Arc<Message>) -> Result<(), ()> {
+        self.last_seq = Some(self.last_seq.map_or(message.seq, |s| s.max(message.seq)));
+        let sender_name = self.sender_name(message.sender_id).await;
+        let delivery = Delivery {
+            message: Arc::unwrap_or_clone(message),
+            sender_name,
+        };
+        self.tx.send(delivery).await.map_err(|_| ())
+    }
+
+    /// Resolve (and cache) the name of a sender.
+    async fn sender_name(&mut self, id: AgentId) -> String {
+        if let Some(name) = self.sender_names.get(&id) {
+            return name.clone();
+        }
+        match self.registry.get_by_id(id).await {
+            Ok(agent) => {
+                self.sender_names.insert(id, agent.name.clone());
+                agent.name
+            }
+            Err(e) => {
+                tracing::warn!("Failed to resolve sender {id}: {e}");
+                id.to_string()
+            }
+        }
+    }
+
+    /// Whether a live message was already sent from the store. Either way the
+    /// message won't show up live again for that attempt, so it is forgotten.
+    fn already_sent(&mut self, message: &Message) -> bool {
+        self.sent
+            .remove(&message.id)
+            .is_some_and(|attempts| attempts == message.delivery_attempts)
+    }
+
+    async fn send_backlog(&mut self, backlog: Vec<Message>) -> Result<(), ()> {
+        for message in backlog {
+            if self.sent.get(&message.id) == Some(&message.delivery_attempts) {
+                continue;
+            }
+            let id = message.id;
+            self.sent.insert(id, message.delivery_attempts);
+            self.send(Arc::new(message)).await?;
+            if let Err(e) = self.store.mark_delivered(id).await {
+                tracing::warn!("Failed to mark message {id} as delivered: {e}");
+            }
+        }
+        Ok(())
+    }
+}
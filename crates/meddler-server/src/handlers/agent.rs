@@ -2,18 +2,20 @@ use std::convert::Infallible;
 
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive},
         Sse,
     },
     Json,
 };
-use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tokio_stream::StreamExt;
 
-use meddler_core::types::{CreateMessage, RegisterAgent};
+use meddler_core::error::Error;
+use meddler_core::types::{CreateMessage, MessageId, RegisterAgent};
 
 use crate::app_state::AppState;
+use crate::delivery;
 
 /// Request body for registering a worker agent.
 #[derive(serde::Deserialize)]
@@ -29,6 +31,15 @@ pub struct AgentMessageRequest {
     pub to: String,
     pub content: String,
     pub task_id: Option<String>,
+    /// ID of the message this one replies to.
+    pub in_reply_to: Option<String>,
+}
+
+/// Request body for a worker agent acknowledging a message.
+#[derive(serde::Deserialize)]
+pub struct AckRequest {
+    pub agent: String,
+    pub message_id: String,
 }
 
 /// Register a worker agent (called by CLI).
@@ -57,6 +68,7 @@ pub async fn agent_register(
 pub async fn agent_sse(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    headers: HeaderMap,
 ) -> Result<Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)>
 {
     // Verify agent exists
@@ -69,16 +81,20 @@ pub async fn agent_sse(
     // Touch last_seen_at
     let _ = state.agent_registry.touch(agent.id).await;
 
-    tracing::info!("Agent '{}' connected via SSE", name);
+    let last_event_id = delivery::last_event_id(&headers);
+    tracing::info!("Agent '{}' connected via SSE (last event id: {:?})", name, last_event_id);
 
-    let rx = state.sessions.subscribe(&name).await;
-    let stream = BroadcastStream::new(rx).filter_map(|result| {
-        result.ok().map(|msg| {
-            Ok(Event::default()
-                .event("message")
-                .json_data(&*msg)
-                .unwrap_or_else(|_| Event::default().data("error serializing message")))
-        })
+    // Drain anything queued while the agent was offline (or missed since
+    // `Last-Event-ID` on reconnect), then go live
+    let inbox = delivery::open_inbox(&state, &agent, last_event_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let stream = inbox.map(|delivery| {
+        Ok(Event::default()
+            .event("message")
+            .id(delivery.message.seq.to_string())
+            .json_data(&delivery)
+            .unwrap_or_else(|_| Event::default().data("error serializing message")))
     });
 
     Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
@@ -117,9 +133,35 @@ pub async fn agent_message(
         })
         .transpose()?;
 
-    // If there's a task, mark it as started
+    let in_reply_to = req
+        .in_reply_to
+        .as_deref()
+        .map(|s| {
+            s.parse::<uuid::Uuid>()
+                .map(MessageId)
+                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid in_reply_to: {e}")))
+        })
+        .transpose()?;
+
+    // A reply has to answer a message that exists
+    if let Some(original) = in_reply_to {
+        state
+            .message_store
+            .get_message(original)
+            .await
+            .map_err(|e| match e {
+                Error::MessageNotFound(_) => (StatusCode::NOT_FOUND, format!("In reply to: {e}")),
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            })?;
+    }
+
+    // If there's a task, mark it as started; closed tasks take no messages
     if let Some(tid) = task_id {
-        let _ = state.task_store.mark_started(tid).await;
+        state.task_store.mark_started(tid).await.map_err(|e| match e {
+            Error::TaskNotFound(_) => (StatusCode::NOT_FOUND, e.to_string()),
+            Error::TaskClosed(..) => (StatusCode::CONFLICT, e.to_string()),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })?;
     }
 
     let message = state
@@ -128,16 +170,52 @@ pub async fn agent_message(
             sender_id: sender.id,
             recipient_id: recipient.id,
             task_id,
+            in_reply_to,
             content: req.content,
         })
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Notify the recipient via SSE
-    let delivered = state.sessions.notify(&req.to, message.clone()).await;
+    // Notify the recipient via SSE (queued as pending if offline)
+    let (message_id, seq) = (message.id, message.seq);
+    let delivered = delivery::dispatch(&state, &req.to, message).await;
 
     Ok(Json(serde_json::json!({
-        "message_id": message.id,
+        "message_id": message_id,
+        "seq": seq,
         "delivered": delivered,
     })))
 }
+
+/// Worker agent confirms it processed a message, so it won't be redelivered.
+#[allow(clippy::missing_errors_doc)]
+pub async fn agent_ack(
+    State(state): State<AppState>,
+    Json(req): Json<AckRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let agent = state
+        .agent_registry
+        .get_by_name(&req.agent)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+
+    let message_id = req
+        .message_id
+        .parse::<uuid::Uuid>()
+        .map(MessageId)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid message_id: {e}")))?;
+
+    let message = state
+        .message_store
+        .acknowledge(message_id, agent.id)
+        .await
+        .map_err(|e| match e {
+            Error::MessageNotFound(_) => (StatusCode::NOT_FOUND, e.to_string()),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })?;
+
+    Ok(Json(serde_json::json!({
+        "message_id": message.id,
+        "delivery_state": message.delivery_state,
+    })))
+}
@@ -1,8 +1,10 @@
 use std::convert::Infallible;
+use std::sync::Arc;
 
 use axum::{
-    extract::State,
-    http::StatusCode,
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive},
         IntoResponse, Response, Sse,
@@ -10,78 +12,178 @@ use axum::{
     Json,
 };
 use serde_json::Value;
-use tokio_stream::{wrappers::BroadcastStream, StreamExt};
-
-use meddler_core::types::{CreateMessage, CreateTask, MessageFilter};
-use meddler_mcp::jsonrpc::{INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND};
-use meddler_mcp::{JsonRpcRequest, JsonRpcResponse, ToolRegistry};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use tokio_stream::StreamExt;
+
+use meddler_core::error::Error;
+use meddler_core::types::Agent;
+use meddler_mcp::jsonrpc::{
+    INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, RESOURCE_NOT_FOUND,
+};
+use meddler_mcp::{
+    JsonRpcRequest, JsonRpcRequestBody, JsonRpcResponse, JsonRpcResponseBody, PromptError,
+    ProtocolVersion, ResourceUri, ORCHESTRATOR_HEADER, PROTOCOL_VERSION_HEADER, SESSION_HEADER,
+};
 
 use crate::app_state::AppState;
-
-const MCP_ORCHESTRATOR_NAME: &str = "__orchestrator__";
+use crate::mcp_session::{self, McpSession};
+use crate::{delivery, prompts, resources};
+use crate::tools::ToolContext;
+
+/// Query string of legacy SSE clients, which POST to the endpoint URL they
+/// were handed by the `endpoint` event.
+#[derive(serde::Deserialize)]
+pub struct SessionQuery {
+    #[serde(rename = "sessionId")]
+    pub session_id: Option<String>,
+}
 
 /// SSE stream for the orchestrator (Cursor/Claude Desktop).
 ///
 /// Kept for the legacy MCP SSE transport. The primary transport is now
-/// Streamable HTTP (POST to the same URL returns JSON directly).
+/// Streamable HTTP (POST to the same URL returns JSON directly). Each
+/// connection gets its own session, and with it its own orchestrator identity
+/// (from the `X-Meddler-Orchestrator` header, or generated); the `endpoint`
+/// event carries the session id for the client's POSTs.
 pub async fn mcp_sse(
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
-    // Register the orchestrator as a special agent if not exists
-    let _ = state
-        .agent_registry
-        .register(meddler_core::types::RegisterAgent {
-            name: MCP_ORCHESTRATOR_NAME.to_string(),
-            description: "MCP orchestrator (Cursor/Claude Desktop)".to_string(),
-        })
+    let label = header_orchestrator(&headers).unwrap_or_else(|| unique_label("legacy-sse"));
+    let session = state
+        .mcp_sessions
+        .create(
+            mcp_session::orchestrator_name(&label),
+            ProtocolVersion::V2024_11_05,
+        )
         .await;
 
-    tracing::info!("Orchestrator connected via MCP SSE");
-
-    let rx = state.sessions.subscribe(MCP_ORCHESTRATOR_NAME).await;
+    tracing::info!(
+        "Orchestrator '{}' connected via MCP SSE",
+        session.orchestrator
+    );
 
     // Send initial endpoint event as required by MCP SSE spec.
     // The endpoint tells the client where to POST JSON-RPC requests.
-    let init_stream = tokio_stream::once(Ok(Event::default()
+    let endpoint = Event::default()
         .event("endpoint")
-        .data("/mcp/sse")));
-
-    let message_stream = BroadcastStream::new(rx).filter_map(|result| {
-        result.ok().map(|msg| {
-            // Wrap message as an MCP notification
-            let notification = serde_json::json!({
-                "jsonrpc": "2.0",
-                "method": "notifications/message",
-                "params": {
-                    "message": *msg,
-                }
-            });
-            Ok(Event::default()
-                .event("message")
-                .json_data(&notification)
-                .unwrap_or_else(|_| Event::default().data("error")))
-        })
-    });
+        .data(format!("/mcp/sse?sessionId={}", session.id));
 
-    let stream = init_stream.chain(message_stream);
+    let events = session_events(&state, session, None, true);
+    let stream = tokio_stream::once(Ok(endpoint)).chain(events);
 
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
+/// Streamable HTTP GET: the session's stream of server-initiated messages.
+///
+/// Carries the notifications the session is sent, such as updates to the
+/// resources it subscribed to, until the session is deleted. Each event has an
+/// id; a client reconnecting with `Last-Event-ID` first gets the recent events
+/// it missed.
+#[allow(clippy::missing_errors_doc)]
+pub async fn mcp_stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)>
+{
+    let Some(session) = resolve_session(&state, session_id(&headers)).await? else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Missing {SESSION_HEADER} header"),
+        ));
+    };
+
+    let last_event_id = delivery::last_event_id(&headers).and_then(|id| u64::try_from(id).ok());
+    tracing::info!(
+        "MCP session {} ('{}') opened its stream (last event id: {last_event_id:?})",
+        session.id,
+        session.orchestrator
+    );
+
+    let events = session_events(&state, session, last_event_id, false);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Streamable HTTP DELETE: the client ends its session.
+pub async fn mcp_delete(State(state): State<AppState>, headers: HeaderMap) -> StatusCode {
+    let Some(id) = session_id(&headers) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    if state.mcp_sessions.remove(id).await {
+        tracing::info!("MCP session {id} ended");
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
 /// Handle MCP JSON-RPC requests from the orchestrator (Streamable HTTP).
 ///
-/// Returns JSON-RPC response directly in the HTTP body for requests,
-/// or 202 Accepted for notifications (no `id` field).
+/// `initialize` starts a session and returns its id in the `Mcp-Session-Id`
+/// header; later requests carrying that header (or the `sessionId` query of
+/// legacy SSE clients) are validated against it. Each session acts as its own
+/// orchestrator agent, named from the `X-Meddler-Orchestrator` header or the
+/// client's `clientInfo`. A second `initialize` within a session is rejected.
+///
+/// Requests without a session id are served statelessly as the header's
+/// orchestrator, or the shared default one. This fallback is kept for clients
+/// that predate sessions and for scripts that never initialize; such requests
+/// get no session-bound features (subscriptions, cancellation, GET stream).
+///
+/// Returns the JSON-RPC response directly in the HTTP body, or — for
+/// `tools/call` when the client accepts `text/event-stream` — as an SSE stream
+/// carrying notifications about the call followed by the response. Returns
+/// 202 Accepted for notifications (no `id` field), and for a `tools/call` the
+/// client cancelled with `notifications/cancelled` while it ran.
+///
+/// Clients on a protocol revision that allows it may also send a batch (a JSON
+/// array of requests), answered with an array holding one response per request
+/// that has an `id`.
+///
+/// Bodies that aren't valid JSON-RPC get a 400 carrying a `PARSE_ERROR` or
+/// `INVALID_REQUEST` error object.
 #[allow(clippy::missing_errors_doc)]
 pub async fn mcp_request(
     State(state): State<AppState>,
-    Json(req): Json<JsonRpcRequest>,
+    Query(query): Query<SessionQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let body = match JsonRpcRequestBody::parse(&body) {
+        Ok(body) => body,
+        Err(rejection) => {
+            tracing::warn!("Rejected MCP request body: {:?}", rejection.error);
+            return (StatusCode::BAD_REQUEST, Json(rejection)).into_response();
+        }
+    };
+
+    let requested_session = session_id(&headers).or(query.session_id.as_deref());
+    match body {
+        JsonRpcRequestBody::Single(req) => {
+            handle_single(state, &headers, requested_session, req).await
+        }
+        JsonRpcRequestBody::Batch(batch) => {
+            handle_batch(state, &headers, requested_session, batch).await
+        }
+    }
+}
+
+async fn handle_single(
+    state: AppState,
+    headers: &HeaderMap,
+    requested_session: Option<&str>,
+    req: JsonRpcRequest,
 ) -> Response {
     tracing::info!("MCP request: method={}", req.method);
 
     // Handle notifications (no id / null id) - no response needed
     if req.id.is_null() {
         tracing::info!("Received MCP notification: {}", req.method);
+        if let Ok(Some(session)) = resolve_session(&state, requested_session).await {
+            handle_notification(&session, &req);
+        }
         return StatusCode::ACCEPTED.into_response();
     }
 
@@ -90,32 +192,406 @@ pub async fn mcp_request(
         return StatusCode::ACCEPTED.into_response();
     }
 
+    let session = match resolve_session(&state, requested_session).await {
+        Ok(None) if req.method == "initialize" => {
+            let label = header_orchestrator(headers)
+                .unwrap_or_else(|| unique_label(client_name(&req).unwrap_or("mcp-client")));
+            let protocol = ProtocolVersion::negotiate(requested_protocol(&req));
+            Some(
+                state
+                    .mcp_sessions
+                    .create(mcp_session::orchestrator_name(&label), protocol)
+                    .await,
+            )
+        }
+        // The version was agreed on when the session started; a client that
+        // wants another one has to start a new session
+        Ok(Some(session)) if req.method == "initialize" => {
+            return Json(JsonRpcResponse::error(
+                req.id,
+                INVALID_REQUEST,
+                format!("MCP session {} is already initialized", session.id),
+            ))
+            .into_response();
+        }
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    // Only hand out the id when this request created the session
+    let session_header = session
+        .as_ref()
+        .filter(|_| req.method == "initialize" && requested_session.is_none())
+        .map(|s| [(SESSION_HEADER, s.id.clone())]);
+
+    let mut ctx = match request_context(&state, headers, session).await {
+        Ok(ctx) => ctx,
+        Err((StatusCode::INTERNAL_SERVER_ERROR, e)) => {
+            return Json(JsonRpcResponse::error(req.id, INTERNAL_ERROR, e)).into_response();
+        }
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    if req.method == "tools/call" && accepts_event_stream(headers) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        ctx.stream = Some(tx.clone());
+        tokio::spawn(async move {
+            // A cancelled call just ends the stream without a response
+            if let Some(response) = handle_cancellable(&ctx, &req).await {
+                let _ = tx.send(serde_json::to_value(response).unwrap_or_default());
+            }
+        });
+        let stream = UnboundedReceiverStream::new(rx)
+            .map(|message| Ok::<_, Infallible>(json_event(&message)));
+        return Sse::new(stream).into_response();
+    }
+
+    let Some(response) = handle_cancellable(&ctx, &req).await else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+
+    (session_header, Json(response)).into_response()
+}
+
+/// Serve a JSON-RPC batch. Its requests run concurrently; notifications in it
+/// are accepted without an entry in the response array.
+async fn handle_batch(
+    state: AppState,
+    headers: &HeaderMap,
+    requested_session: Option<&str>,
+    batch: Vec<Result<JsonRpcRequest, JsonRpcResponse>>,
+) -> Response {
+    tracing::info!("MCP batch of {} requests", batch.len());
+
+    let session = match resolve_session(&state, requested_session).await {
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let ctx = match request_context(&state, headers, session).await {
+        Ok(ctx) => Arc::new(ctx),
+        Err((StatusCode::INTERNAL_SERVER_ERROR, e)) => {
+            return Json(JsonRpcResponse::error(Value::Null, INTERNAL_ERROR, e)).into_response();
+        }
+        Err(rejection) => return rejection.into_response(),
+    };
+    if !ctx.protocol.supports_batch() {
+        return Json(JsonRpcResponse::error(
+            Value::Null,
+            INVALID_REQUEST,
+            format!(
+                "Batch requests are not supported in protocol version {}",
+                ctx.protocol
+            ),
+        ))
+        .into_response();
+    }
+
+    let calls: Vec<_> = batch
+        .into_iter()
+        .map(|req| {
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                let req = match req {
+                    Ok(req) => req,
+                    Err(rejection) => return Some(rejection),
+                };
+                if req.id.is_null() {
+                    if let Some(session) = &ctx.session {
+                        handle_notification(session, &req);
+                    }
+                    return None;
+                }
+                if req.method == "notifications/initialized" {
+                    return None;
+                }
+                // The session has to exist before anything else can be sent
+                if req.method == "initialize" {
+                    return Some(JsonRpcResponse::error(
+                        req.id,
+                        INVALID_REQUEST,
+                        "initialize must not be part of a batch",
+                    ));
+                }
+                handle_cancellable(&ctx, &req).await
+            })
+        })
+        .collect();
+
+    let mut responses = Vec::with_capacity(calls.len());
+    for call in calls {
+        match call.await {
+            Ok(response) => responses.push(response),
+            Err(e) => tracing::error!("MCP batch request panicked: {e}"),
+        }
+    }
+
+    match JsonRpcResponseBody::batch(responses) {
+        Some(body) => Json(body).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// Work out who a request is from and which protocol revision it speaks, and
+/// make sure its orchestrator is registered.
+async fn request_context(
+    state: &AppState,
+    headers: &HeaderMap,
+    session: Option<Arc<McpSession>>,
+) -> Result<ToolContext, (StatusCode, String)> {
+    let protocol = match &session {
+        Some(session) => session.protocol_version(),
+        None => header_protocol_version(headers)?,
+    };
+
+    let orchestrator = match &session {
+        Some(session) => session.orchestrator.clone(),
+        None => header_orchestrator(headers).map_or_else(
+            || mcp_session::DEFAULT_ORCHESTRATOR.to_string(),
+            |label| mcp_session::orchestrator_name(&label),
+        ),
+    };
+
     // Ensure orchestrator agent is registered
-    let _ = state
+    register_orchestrator(state, &orchestrator).await?;
+
+    Ok(ToolContext {
+        state: state.clone(),
+        session,
+        orchestrator,
+        protocol,
+        progress_token: None,
+        stream: None,
+    })
+}
+
+/// Act on a notification from a session's client.
+fn handle_notification(session: &McpSession, req: &JsonRpcRequest) {
+    if req.method != "notifications/cancelled" {
+        return;
+    }
+    let Some(request_id) = req.params.as_ref().and_then(|p| p.get("requestId")) else {
+        return;
+    };
+    let reason = req
+        .params
+        .as_ref()
+        .and_then(|p| p.get("reason"))
+        .and_then(Value::as_str)
+        .unwrap_or("no reason given");
+    if session.cancel_request(request_id) {
+        tracing::info!("MCP session {} cancelled request {request_id}: {reason}", session.id);
+    } else {
+        tracing::debug!(
+            "MCP session {} cancelled request {request_id}, which isn't running",
+            session.id
+        );
+    }
+}
+
+/// Serve a request, unless the client cancels it first with
+/// `notifications/cancelled`. Returns `None` for a cancelled request, which
+/// gets no response. Only `tools/call` within a session can be cancelled;
+/// dropping the call aborts it along with any wait for replies.
+async fn handle_cancellable(ctx: &ToolContext, req: &JsonRpcRequest) -> Option<JsonRpcResponse> {
+    let Some(session) = ctx.session.as_ref().filter(|_| req.method == "tools/call") else {
+        return Some(handle_method(ctx, req).await);
+    };
+
+    let cancelled = session.track_request(&req.id);
+    let response = tokio::select! {
+        response = handle_method(ctx, req) => Some(response),
+        Ok(()) = cancelled => None,
+    };
+    session.finish_request(&req.id);
+    response
+}
+
+async fn handle_method(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
+    match req.method.as_str() {
+        "initialize" => handle_initialize(ctx, req),
+        "logging/setLevel" => JsonRpcResponse::success(req.id.clone(), serde_json::json!({})),
+        "tools/list" => handle_tools_list(ctx, req),
+        "tools/call" => handle_tools_call(ctx, req).await,
+        "resources/list" => handle_resources_list(ctx, req).await,
+        "resources/templates/list" => JsonRpcResponse::success(
+            req.id.clone(),
+            serde_json::json!({ "resourceTemplates": ResourceUri::templates() }),
+        ),
+        "resources/read" => handle_resources_read(ctx, req).await,
+        "resources/subscribe" => handle_resources_subscribe(ctx, req, true).await,
+        "resources/unsubscribe" => handle_resources_subscribe(ctx, req, false).await,
+        "prompts/list" => handle_prompts_list(ctx, req).await,
+        "prompts/get" => handle_prompts_get(ctx, req).await,
+        _ => JsonRpcResponse::error(req.id.clone(), METHOD_NOT_FOUND, "Method not found"),
+    }
+}
+
+/// Read the `Mcp-Session-Id` header.
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok())
+}
+
+/// Read the orchestrator label a client picked via `X-Meddler-Orchestrator`.
+fn header_orchestrator(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(ORCHESTRATOR_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// The `clientInfo.name` an `initialize` request announces.
+fn client_name(req: &JsonRpcRequest) -> Option<&str> {
+    req.params
+        .as_ref()?
+        .get("clientInfo")?
+        .get("name")?
+        .as_str()
+        .filter(|name| !name.is_empty())
+}
+
+/// The `protocolVersion` an `initialize` request asks for.
+fn requested_protocol(req: &JsonRpcRequest) -> Option<&str> {
+    req.params.as_ref()?.get("protocolVersion")?.as_str()
+}
+
+/// Read the `MCP-Protocol-Version` header of a request outside a session.
+/// Clients that don't send it are assumed to speak the spec's default; ones
+/// asking for a revision we don't know are rejected.
+fn header_protocol_version(headers: &HeaderMap) -> Result<ProtocolVersion, (StatusCode, String)> {
+    let Some(value) = headers.get(PROTOCOL_VERSION_HEADER) else {
+        return Ok(ProtocolVersion::HTTP_DEFAULT);
+    };
+    value
+        .to_str()
+        .map_err(|e| e.to_string())
+        .and_then(str::parse)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// A label that won't collide with other clients of the same kind, so two
+/// people running the same IDE don't share an identity.
+fn unique_label(base: &str) -> String {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    format!("{base}-{}", &suffix[..8])
+}
+
+/// Look up a session by id and mark it as in use. Unknown (e.g. deleted or
+/// expired) sessions are a 404, telling the client to re-initialize.
+async fn resolve_session(
+    state: &AppState,
+    id: Option<&str>,
+) -> Result<Option<Arc<McpSession>>, (StatusCode, String)> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let session = state
+        .mcp_sessions
+        .get(id)
+        .await
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Unknown MCP session: {id}")))?;
+    session.touch();
+    Ok(Some(session))
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains("text/event-stream"))
+}
+
+/// Register an orchestrator as a special agent if it doesn't exist yet.
+async fn register_orchestrator(
+    state: &AppState,
+    name: &str,
+) -> Result<Agent, (StatusCode, String)> {
+    state
         .agent_registry
         .register(meddler_core::types::RegisterAgent {
-            name: MCP_ORCHESTRATOR_NAME.to_string(),
+            name: name.to_string(),
             description: "MCP orchestrator (Cursor/Claude Desktop)".to_string(),
         })
-        .await;
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
 
-    let response = match req.method.as_str() {
-        "initialize" => handle_initialize(&req),
-        "tools/list" => handle_tools_list(&req),
-        "tools/call" => handle_tools_call(&state, &req).await,
-        _ => JsonRpcResponse::error(req.id, METHOD_NOT_FOUND, "Method not found"),
+/// Events for a session's stream: the notifications sent to the session,
+/// starting after `last_event_id` when the client resumes. The stream ends
+/// when the session does; with `owns_session`, the session ends when the
+/// stream does.
+///
+/// A stream that falls too far behind is closed rather than silently skipping
+/// events, so the client reconnects and resumes from the last id it saw.
+fn session_events(
+    state: &AppState,
+    session: Arc<McpSession>,
+    last_event_id: Option<u64>,
+    owns_session: bool,
+) -> ReceiverStream<Result<Event, Infallible>> {
+    let (missed, mut outbound) = match last_event_id {
+        Some(id) => session.resume(id),
+        None => (Vec::new(), session.subscribe()),
     };
+    let session_id = session.id.clone();
+    drop(session);
+
+    let sessions = state.mcp_sessions.clone();
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut missed = missed.into_iter();
+        loop {
+            let event = if let Some(event) = missed.next() {
+                event
+            } else {
+                tokio::select! {
+                    () = tx.closed() => break,
+                    outbound = outbound.recv() => match outbound {
+                        Ok(event) => event,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            tracing::warn!(
+                                "MCP session {session_id} stream lagged by {skipped} events, \
+                                 closing it so the client resumes"
+                            );
+                            break;
+                        }
+                        // The session was deleted
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            };
+            let event = json_event(&event.message).id(event.id.to_string());
+            if tx.send(Ok(event)).await.is_err() {
+                break;
+            }
+        }
+        if owns_session {
+            sessions.remove(&session_id).await;
+        }
+    });
 
-    Json(response).into_response()
+    ReceiverStream::new(rx)
 }
 
-fn handle_initialize(req: &JsonRpcRequest) -> JsonRpcResponse {
+fn json_event(message: &Value) -> Event {
+    Event::default()
+        .event("message")
+        .json_data(message)
+        .unwrap_or_else(|_| Event::default().data("error"))
+}
+
+fn handle_initialize(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
     JsonRpcResponse::success(
         req.id.clone(),
         serde_json::json!({
-            "protocolVersion": "2024-11-05",
+            "protocolVersion": ctx.protocol.as_str(),
             "capabilities": {
-                "tools": {}
+                "tools": {},
+                "resources": { "subscribe": true },
+                "prompts": {},
+                "logging": {}
             },
             "serverInfo": {
                 "name": "meddler",
@@ -125,15 +601,21 @@ fn handle_initialize(req: &JsonRpcRequest) -> JsonRpcResponse {
     )
 }
 
-fn handle_tools_list(req: &JsonRpcRequest) -> JsonRpcResponse {
-    let tools = ToolRegistry::definitions();
+fn handle_tools_list(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
+    let tools: Vec<_> = ctx
+        .state
+        .tools
+        .definitions()
+        .into_iter()
+        .map(|tool| tool.for_protocol(ctx.protocol))
+        .collect();
     JsonRpcResponse::success(
         req.id.clone(),
         serde_json::json!({ "tools": tools }),
     )
 }
 
-async fn handle_tools_call(state: &AppState, req: &JsonRpcRequest) -> JsonRpcResponse {
+async fn handle_tools_call(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
     let Some(params) = &req.params else {
         return JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, "Missing params");
     };
@@ -148,210 +630,228 @@ async fn handle_tools_call(state: &AppState, req: &JsonRpcRequest) -> JsonRpcRes
         .cloned()
         .unwrap_or(Value::Object(serde_json::Map::new()));
 
-    let result = match tool_name {
-        "list_agents" => tool_list_agents(state).await,
-        "send_message" => tool_send_message(state, &arguments).await,
-        "get_messages" => tool_get_messages(state, &arguments).await,
-        "create_task" => tool_create_task(state, &arguments).await,
-        "get_task_status" => tool_get_task_status(state, &arguments).await,
-        _ => Err(format!("Unknown tool: {tool_name}")),
+    let ctx = ToolContext {
+        progress_token: params
+            .get("_meta")
+            .and_then(|meta| meta.get("progressToken"))
+            .filter(|token| token.is_string() || token.is_number())
+            .cloned(),
+        ..ctx.clone()
     };
+    let result = ctx.state.tools.call(&ctx, tool_name, arguments).await;
 
     match result {
-        Ok(value) => JsonRpcResponse::success(
-            req.id.clone(),
-            serde_json::json!({
+        Ok(value) => {
+            let mut result = serde_json::json!({
                 "content": [{
                     "type": "text",
                     "text": serde_json::to_string_pretty(&value).unwrap_or_default()
                 }]
-            }),
-        ),
-        Err(err) => JsonRpcResponse::error(req.id.clone(), INTERNAL_ERROR, err),
+            });
+            // Structured results have to be objects; the text block stays for
+            // clients that don't read them
+            if ctx.protocol.supports_structured_output() && value.is_object() {
+                result["structuredContent"] = value;
+            }
+            JsonRpcResponse::success(req.id.clone(), result)
+        }
+        Err(err) if err.is_protocol_error() => {
+            JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, err.to_string())
+        }
+        Err(err) => {
+            tracing::info!("Tool '{tool_name}' failed: {err}");
+            JsonRpcResponse::success(req.id.clone(), err.to_result())
+        }
     }
 }
 
-async fn tool_list_agents(state: &AppState) -> Result<Value, String> {
-    let agents = state
-        .agent_registry
-        .list()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    // Filter out the internal orchestrator agent
-    let mut agent_list = Vec::new();
-    for a in agents {
-        if a.name == MCP_ORCHESTRATOR_NAME {
-            continue;
-        }
-        let connected = state.sessions.is_connected(&a.name).await;
-        agent_list.push(serde_json::json!({
-            "name": a.name,
-            "description": a.description,
-            "connected": connected,
-        }));
+async fn handle_resources_list(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
+    match resources::list(&ctx.state).await {
+        Ok(resources) => JsonRpcResponse::success(
+            req.id.clone(),
+            serde_json::json!({ "resources": resources }),
+        ),
+        Err(e) => JsonRpcResponse::error(req.id.clone(), INTERNAL_ERROR, e.to_string()),
     }
-    let agents = agent_list;
-
-    Ok(serde_json::json!({ "agents": agents }))
 }
 
-async fn tool_send_message(state: &AppState, args: &Value) -> Result<Value, String> {
-    let to = args
-        .get("to")
+async fn handle_resources_read(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
+    let Some(uri) = req
+        .params
+        .as_ref()
+        .and_then(|p| p.get("uri"))
         .and_then(Value::as_str)
-        .ok_or("Missing 'to' parameter")?;
-
-    let content = args
-        .get("content")
-        .and_then(Value::as_str)
-        .ok_or("Missing 'content' parameter")?;
-
-    let task_id = args
-        .get("task_id")
-        .and_then(Value::as_str)
-        .map(|s| {
-            s.parse::<uuid::Uuid>()
-                .map(meddler_core::types::TaskId)
-                .map_err(|e| format!("Invalid task_id: {e}"))
-        })
-        .transpose()?;
+    else {
+        return JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, "Missing 'uri' parameter");
+    };
 
-    // Resolve orchestrator as sender
-    let sender = state
-        .agent_registry
-        .get_by_name(MCP_ORCHESTRATOR_NAME)
-        .await
-        .map_err(|e| e.to_string())?;
+    let resource = match uri.parse::<ResourceUri>() {
+        Ok(resource) => resource,
+        Err(e) => return JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, e),
+    };
 
-    // Resolve recipient
-    let recipient = state
-        .agent_registry
-        .get_by_name(to)
-        .await
-        .map_err(|e| format!("Recipient agent '{to}' not found: {e}"))?;
+    let contents = match &resource {
+        // An inbox is private to the orchestrator it belongs to
+        ResourceUri::AgentInbox(name) if *name != ctx.orchestrator => {
+            Err(Error::AgentNotFound(name.clone()))
+        }
+        _ => resources::read(&ctx.state, &resource).await,
+    };
 
-    // If there's a task, mark it as started
-    if let Some(tid) = task_id {
-        let _ = state.task_store.mark_started(tid).await;
+    match contents {
+        Ok(contents) => JsonRpcResponse::success(
+            req.id.clone(),
+            serde_json::json!({ "contents": [contents] }),
+        ),
+        Err(e @ (Error::AgentNotFound(_) | Error::TaskNotFound(_))) => {
+            JsonRpcResponse::error_with_data(
+                req.id.clone(),
+                RESOURCE_NOT_FOUND,
+                "Resource not found",
+                serde_json::json!({ "uri": uri, "reason": e.to_string() }),
+            )
+        }
+        Err(e) => JsonRpcResponse::error(req.id.clone(), INTERNAL_ERROR, e.to_string()),
     }
-
-    let message = state
-        .message_store
-        .create(CreateMessage {
-            sender_id: sender.id,
-            recipient_id: recipient.id,
-            task_id,
-            content: content.to_string(),
-        })
-        .await
-        .map_err(|e| e.to_string())?;
-
-    // Push to recipient's SSE
-    let delivered = state.sessions.notify(to, message.clone()).await;
-
-    Ok(serde_json::json!({
-        "message_id": message.id,
-        "delivered": delivered,
-    }))
 }
 
-async fn tool_get_messages(state: &AppState, args: &Value) -> Result<Value, String> {
-    let task_id = args
-        .get("task_id")
-        .and_then(Value::as_str)
-        .map(|s| {
-            s.parse::<uuid::Uuid>()
-                .map(meddler_core::types::TaskId)
-                .map_err(|e| format!("Invalid task_id: {e}"))
-        })
-        .transpose()?;
-
-    let sender_id = if let Some(name) = args.get("sender").and_then(Value::as_str) {
-        Some(
-            state
-                .agent_registry
-                .get_by_name(name)
-                .await
-                .map_err(|e| format!("Sender '{name}' not found: {e}"))?
-                .id,
-        )
-    } else {
-        None
+/// `resources/subscribe` and `resources/unsubscribe`. Task transcripts and
+/// the session's own inbox are the resources that change over time, so they
+/// are the only ones that can be subscribed to; updates go out on the
+/// session's stream. Subscribing to an inbox that already holds messages sends
+/// an update straight away, so nothing that arrived while the orchestrator was
+/// away goes unnoticed.
+async fn handle_resources_subscribe(
+    ctx: &ToolContext,
+    req: &JsonRpcRequest,
+    subscribe: bool,
+) -> JsonRpcResponse {
+    let Some(session) = &ctx.session else {
+        return JsonRpcResponse::error(
+            req.id.clone(),
+            INVALID_REQUEST,
+            "Subscriptions require a session; send initialize first",
+        );
     };
 
-    let recipient_id = if let Some(name) = args.get("recipient").and_then(Value::as_str) {
-        Some(
-            state
-                .agent_registry
-                .get_by_name(name)
-                .await
-                .map_err(|e| format!("Recipient '{name}' not found: {e}"))?
-                .id,
-        )
-    } else {
-        None
+    let Some(uri) = req
+        .params
+        .as_ref()
+        .and_then(|p| p.get("uri"))
+        .and_then(Value::as_str)
+    else {
+        return JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, "Missing 'uri' parameter");
     };
 
-    let messages = state
-        .message_store
-        .query(MessageFilter {
-            task_id,
-            sender_id,
-            recipient_id,
-        })
-        .await
-        .map_err(|e| e.to_string())?;
+    let resource = match uri.parse::<ResourceUri>() {
+        Ok(resource @ (ResourceUri::TaskTranscript(_) | ResourceUri::AgentInbox(_))) => resource,
+        Ok(_) => {
+            return JsonRpcResponse::error(
+                req.id.clone(),
+                INVALID_PARAMS,
+                format!("Only task transcripts and inboxes can be subscribed to: {uri}"),
+            )
+        }
+        Err(e) => return JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, e),
+    };
 
-    Ok(serde_json::json!({ "messages": messages }))
-}
+    if !subscribe {
+        session.unsubscribe_resource(&resource);
+        return JsonRpcResponse::success(req.id.clone(), serde_json::json!({}));
+    }
 
-async fn tool_create_task(state: &AppState, args: &Value) -> Result<Value, String> {
-    let title = args
-        .get("title")
-        .and_then(Value::as_str)
-        .ok_or("Missing 'title' parameter")?;
+    // Whether the resource exists, and if so whether it already has news
+    let has_news = match &resource {
+        ResourceUri::TaskTranscript(task_id) => {
+            ctx.state.task_store.get(*task_id).await.map(|_| false)
+        }
+        ResourceUri::AgentInbox(name) if *name == ctx.orchestrator => {
+            inbox_has_messages(&ctx.state, name).await
+        }
+        ResourceUri::AgentInbox(name) | ResourceUri::Agent(name) => {
+            Err(Error::AgentNotFound(name.clone()))
+        }
+        ResourceUri::Task(task_id) => Err(Error::TaskNotFound(*task_id)),
+    };
 
-    let time_budget_secs = args.get("time_budget_secs").and_then(Value::as_i64);
+    match has_news {
+        Ok(has_news) => {
+            session.subscribe_resource(resource);
+            if has_news {
+                session.notify(
+                    "notifications/resources/updated",
+                    serde_json::json!({ "uri": uri }),
+                );
+            }
+            JsonRpcResponse::success(req.id.clone(), serde_json::json!({}))
+        }
+        Err(e @ (Error::AgentNotFound(_) | Error::TaskNotFound(_))) => {
+            JsonRpcResponse::error_with_data(
+                req.id.clone(),
+                RESOURCE_NOT_FOUND,
+                "Resource not found",
+                serde_json::json!({ "uri": uri, "reason": e.to_string() }),
+            )
+        }
+        Err(e) => JsonRpcResponse::error(req.id.clone(), INTERNAL_ERROR, e.to_string()),
+    }
+}
 
-    // Resolve orchestrator as creator
-    let creator = state
-        .agent_registry
-        .get_by_name(MCP_ORCHESTRATOR_NAME)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let task = state
-        .task_store
-        .create(CreateTask {
-            title: title.to_string(),
-            created_by: creator.id,
-            time_budget_secs,
-        })
-        .await
-        .map_err(|e| e.to_string())?;
+/// Whether messages are waiting in an orchestrator's inbox.
+async fn inbox_has_messages(state: &AppState, orchestrator: &str) -> Result<bool, Error> {
+    let orchestrator = state.agent_registry.get_by_name(orchestrator).await?;
+    let waiting = state.message_store.unacknowledged_for(orchestrator.id).await?;
+    Ok(!waiting.is_empty())
+}
 
-    Ok(serde_json::json!({
-        "task_id": task.id,
-        "title": task.title,
-    }))
+async fn handle_prompts_list(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
+    match prompts::list(&ctx.state).await {
+        Ok(prompts) => {
+            JsonRpcResponse::success(req.id.clone(), serde_json::json!({ "prompts": prompts }))
+        }
+        Err(e) => JsonRpcResponse::error(req.id.clone(), INTERNAL_ERROR, e.to_string()),
+    }
 }
 
-async fn tool_get_task_status(state: &AppState, args: &Value) -> Result<Value, String> {
-    let task_id = args
-        .get("task_id")
+async fn handle_prompts_get(ctx: &ToolContext, req: &JsonRpcRequest) -> JsonRpcResponse {
+    let Some(name) = req
+        .params
+        .as_ref()
+        .and_then(|p| p.get("name"))
         .and_then(Value::as_str)
-        .ok_or("Missing 'task_id' parameter")?;
-
-    let id: uuid::Uuid = task_id
-        .parse()
-        .map_err(|e| format!("Invalid task_id: {e}"))?;
+    else {
+        return JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, "Missing 'name' parameter");
+    };
 
-    let status = state
-        .task_store
-        .get_status(meddler_core::types::TaskId(id))
-        .await
-        .map_err(|e| e.to_string())?;
+    let arguments = req
+        .params
+        .as_ref()
+        .and_then(|p| p.get("arguments"))
+        .cloned()
+        .unwrap_or_default();
+    let arguments = if arguments.is_null() {
+        std::collections::BTreeMap::new()
+    } else {
+        match serde_json::from_value(arguments) {
+            Ok(arguments) => arguments,
+            Err(e) => {
+                return JsonRpcResponse::error(
+                    req.id.clone(),
+                    INVALID_PARAMS,
+                    format!("'arguments' must map names to strings: {e}"),
+                )
+            }
+        }
+    };
 
-    Ok(serde_json::json!(status))
+    match prompts::get(&ctx.state, name, &arguments).await {
+        Ok(prompt) => JsonRpcResponse::success(
+            req.id.clone(),
+            serde_json::to_value(prompt).unwrap_or_default(),
+        ),
+        Err(e @ PromptError::Internal(_)) => {
+            JsonRpcResponse::error(req.id.clone(), INTERNAL_ERROR, e.to_string())
+        }
+        Err(e) => JsonRpcResponse::error(req.id.clone(), INVALID_PARAMS, e.to_string()),
+    }
 }
@@ -2,6 +2,6 @@ mod agent;
 mod health;
 mod mcp;
 
-pub use agent::{agent_message, agent_register, agent_sse};
+pub use agent::{agent_ack, agent_message, agent_register, agent_sse};
 pub use health::health;
-pub use mcp::{mcp_request, mcp_sse};
+pub use mcp::{mcp_delete, mcp_request, mcp_sse, mcp_stream};
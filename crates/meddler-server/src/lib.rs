@@ -1,4 +1,12 @@
 pub mod app_state;
+pub mod budget;
+pub mod delivery;
 pub mod handlers;
+pub mod mcp_session;
+pub mod prompts;
+pub mod redelivery;
+pub mod resources;
 pub mod router;
 pub mod session;
+pub mod session_reaper;
+pub mod tools;
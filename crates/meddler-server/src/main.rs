@@ -4,7 +4,11 @@ use sqlx::postgres::PgPoolOptions;
 use tracing_subscriber::EnvFilter;
 
 use meddler_server::app_state::AppState;
-use meddler_server::session;
+use meddler_server::budget::{self, BudgetConfig};
+use meddler_server::mcp_session::McpSessionManager;
+use meddler_server::redelivery::{self, RedeliveryConfig};
+use meddler_server::session_reaper::{self, SessionReaperConfig};
+use meddler_server::{session, tools};
 
 use meddler_store::PgStore;
 
@@ -35,8 +39,35 @@ async fn main() {
         message_store: Arc::new(store.clone()),
         task_store: Arc::new(store),
         sessions: Arc::new(session::SessionManager::new()),
+        mcp_sessions: Arc::new(McpSessionManager::new()),
+        tools: Arc::new(tools::builtin()),
     };
 
+    if let Some(config) = RedeliveryConfig::from_env() {
+        tracing::info!(
+            "Redelivering unacknowledged messages after {}s",
+            config.visibility_timeout.as_secs()
+        );
+        redelivery::spawn(state.clone(), config);
+    }
+
+    if let Some(config) = BudgetConfig::from_env() {
+        tracing::info!(
+            "Checking task budgets every {}s, warning at {:?}%",
+            config.check_interval.as_secs(),
+            config.warn_at_percent
+        );
+        budget::spawn(state.clone(), config);
+    }
+
+    if let Some(config) = SessionReaperConfig::from_env() {
+        tracing::info!(
+            "Ending MCP sessions idle for {}s",
+            config.idle_timeout.as_secs()
+        );
+        session_reaper::spawn(state.clone(), config);
+    }
+
     let app = meddler_server::router::create_router(state);
 
     let addr = format!("{host}:{port}");
@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::sync::{broadcast, oneshot, RwLock};
+
+use meddler_core::types::SYSTEM_AGENT;
+use meddler_mcp::{ProtocolVersion, ResourceUri};
+
+/// How many recent events a session keeps for clients resuming its stream.
+const REPLAY_BUFFER: usize = 256;
+
+/// Agent name of the orchestrator for clients without a session or identity.
+pub const DEFAULT_ORCHESTRATOR: &str = "__orchestrator__";
+
+/// Whether an agent name belongs to an MCP orchestrator rather than a worker.
+pub fn is_orchestrator(name: &str) -> bool {
+    name.strip_prefix(DEFAULT_ORCHESTRATOR)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+}
+
+/// Whether an agent name belongs to a worker, i.e. neither an orchestrator nor
+/// the server's own [`SYSTEM_AGENT`].
+pub fn is_worker(name: &str) -> bool {
+    !is_orchestrator(name) && name != SYSTEM_AGENT
+}
+
+/// Agent name of the orchestrator identified by `label`.
+pub fn orchestrator_name(label: &str) -> String {
+    format!("{DEFAULT_ORCHESTRATOR}:{label}")
+}
+
+/// A server-initiated message on a session's stream. Ids increase by one per
+/// event, so a client can resume after the last one it saw.
+#[derive(Debug)]
+pub struct SessionEvent {
+    pub id: u64,
+    pub message: Value,
+}
+
+/// The latest events sent to a session, for replay.
+#[derive(Default)]
+struct Replay {
+    last_id: u64,
+    events: VecDeque<Arc<SessionEvent>>,
+}
+
+/// One MCP client session, created by `initialize` on the Streamable HTTP
+/// transport or by opening the legacy SSE stream.
+pub struct McpSession {
+    pub id: String,
+    /// Agent name this session sends and receives messages as.
+    pub orchestrator: String,
+    /// Protocol revision negotiated by the session's `initialize`.
+    protocol_version: ProtocolVersion,
+    /// Resources the client asked to hear about via `resources/subscribe`.
+    subscriptions: Mutex<HashSet<ResourceUri>>,
+    /// Cancellable requests still running, by JSON-RPC id.
+    in_flight: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    /// Server-initiated JSON-RPC messages for the session's GET stream.
+    outbound: broadcast::Sender<Arc<SessionEvent>>,
+    /// The last [`REPLAY_BUFFER`] events, for streams resuming after a drop.
+    replay: Mutex<Replay>,
+    /// When the client last sent a request in the session.
+    last_seen: Mutex<Instant>,
+}
+
+impl McpSession {
+    fn new(orchestrator: String, protocol_version: ProtocolVersion) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            orchestrator,
+            protocol_version,
+            subscriptions: Mutex::new(HashSet::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            outbound: broadcast::channel(100).0,
+            replay: Mutex::new(Replay::default()),
+            last_seen: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Record that the client is still using the session.
+    pub fn touch(&self) {
+        *self
+            .last_seen
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Instant::now();
+    }
+
+    /// Whether the client has gone quiet for `max_idle`: no request in that
+    /// time, none still running and no stream open.
+    pub fn is_idle(&self, max_idle: Duration) -> bool {
+        let last_seen = *self
+            .last_seen
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        last_seen.elapsed() >= max_idle
+            && self.outbound.receiver_count() == 0
+            && self.in_flight().is_empty()
+    }
+
+    /// The protocol revision the session speaks.
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    /// Start sending `notifications/resources/updated` for a resource.
+    pub fn subscribe_resource(&self, uri: ResourceUri) {
+        self.subscriptions().insert(uri);
+    }
+
+    /// Stop sending updates for a resource. Returns false if the session
+    /// wasn't subscribed to it.
+    pub fn unsubscribe_resource(&self, uri: &ResourceUri) -> bool {
+        self.subscriptions().remove(uri)
+    }
+
+    /// Whether the session subscribed to a resource.
+    pub fn is_subscribed_to(&self, uri: &ResourceUri) -> bool {
+        self.subscriptions().contains(uri)
+    }
+
+    fn subscriptions(&self) -> std::sync::MutexGuard<'_, HashSet<ResourceUri>> {
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Track a running request so `notifications/cancelled` can stop it. The
+    /// returned receiver fires if the client cancels the request.
+    pub fn track_request(&self, id: &Value) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.in_flight().insert(id.to_string(), tx);
+        rx
+    }
+
+    /// Stop tracking a request once it has finished.
+    pub fn finish_request(&self, id: &Value) {
+        self.in_flight().remove(&id.to_string());
+    }
+
+    /// Cancel a running request. Returns false if no request with that id is
+    /// in flight, e.g. because it already finished.
+    pub fn cancel_request(&self, id: &Value) -> bool {
+        self.in_flight()
+            .remove(&id.to_string())
+            .is_some_and(|cancel| cancel.send(()).is_ok())
+    }
+
+    fn in_flight(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<()>>> {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Queue a JSON-RPC notification for the session's GET stream, keeping
+    /// it for replay. Returns true if a stream is open to receive it.
+    pub fn notify(&self, method: &str, params: Value) -> bool {
+        let mut replay = self.replay();
+        replay.last_id += 1;
+        let event = Arc::new(SessionEvent {
+            id: replay.last_id,
+            message: notification(method, params),
+        });
+        if replay.events.len() == REPLAY_BUFFER {
+            replay.events.pop_front();
+        }
+        replay.events.push_back(event.clone());
+        // Sent under the lock so a resuming stream sees each event once
+        self.outbound.send(event).is_ok()
+    }
+
+    /// Subscribe to the session's server-initiated messages. The receiver is
+    /// closed once the session ends.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<SessionEvent>> {
+        self.outbound.subscribe()
+    }
+
+    /// Subscribe for a client resuming its stream after `last_event_id`:
+    /// returns the kept events it missed, followed on the receiver by
+    /// everything sent from now on. Events older than the replay buffer are
+    /// gone; clients re-read their resources to catch up on those.
+    pub fn resume(
+        &self,
+        last_event_id: u64,
+    ) -> (Vec<Arc<SessionEvent>>, broadcast::Receiver<Arc<SessionEvent>>) {
+        let replay = self.replay();
+        let missed = replay
+            .events
+            .iter()
+            .filter(|event| event.id > last_event_id)
+            .cloned()
+            .collect();
+        (missed, self.outbound.subscribe())
+    }
+
+    fn replay(&self) -> std::sync::MutexGuard<'_, Replay> {
+        self.replay
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Build a JSON-RPC notification.
+pub fn notification(method: &str, params: Value) -> Value {
+    let mut notification = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+    });
+    notification["params"] = params;
+    notification
+}
+
+/// Tracks live MCP sessions by id.
+pub struct McpSessionManager {
+    sessions: RwLock<HashMap<String, Arc<McpSession>>>,
+}
+
+impl McpSessionManager {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Start a new session with a fresh id, acting as the given orchestrator.
+    pub async fn create(
+        &self,
+        orchestrator: String,
+        protocol_version: ProtocolVersion,
+    ) -> Arc<McpSession> {
+        let session = Arc::new(McpSession::new(orchestrator, protocol_version));
+        self.sessions
+            .write()
+            .await
+            .insert(session.id.clone(), session.clone());
+        session
+    }
+
+    /// Look up a live session.
+    pub async fn get(&self, id: &str) -> Option<Arc<McpSession>> {
+        self.sessions.read().await.get(id).cloned()
+    }
+
+    /// Tell every session subscribed to a resource that it changed. Returns
+    /// how many sessions were notified.
+    pub async fn resource_updated(&self, uri: &ResourceUri) -> usize {
+        let sessions = self.sessions.read().await;
+        let subscribers = sessions.values().filter(|s| s.is_subscribed_to(uri));
+        let mut notified = 0;
+        for session in subscribers {
+            session.notify(
+                "notifications/resources/updated",
+                serde_json::json!({ "uri": uri.to_string() }),
+            );
+            notified += 1;
+        }
+        notified
+    }
+
+    /// Whether any live session acts as `orchestrator`.
+    pub async fn has_orchestrator(&self, orchestrator: &str) -> bool {
+        let sessions = self.sessions.read().await;
+        sessions.values().any(|s| s.orchestrator == orchestrator)
+    }
+
+    /// Send a notification to every session acting as `orchestrator`.
+    /// Returns how many sessions were notified.
+    pub async fn notify_orchestrator(
+        &self,
+        orchestrator: &str,
+        method: &str,
+        params: Value,
+    ) -> usize {
+        let sessions = self.sessions.read().await;
+        sessions
+            .values()
+            .filter(|s| s.orchestrator == orchestrator)
+            .filter(|s| s.notify(method, params.clone()))
+            .count()
+    }
+
+    /// End every session that has been idle for `max_idle`, e.g. because its
+    /// client exited without a DELETE. Returns how many were ended.
+    pub async fn evict_idle(&self, max_idle: Duration) -> usize {
+        let mut sessions = self.sessions.write().await;
+        let before = sessions.len();
+        sessions.retain(|_, session| !session.is_idle(max_idle));
+        before - sessions.len()
+    }
+
+    /// End a session. Returns false if it didn't exist.
+    pub async fn remove(&self, id: &str) -> bool {
+        self.sessions.write().await.remove(id).is_some()
+    }
+}
+
+impl Default for McpSessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
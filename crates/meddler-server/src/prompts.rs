@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use meddler_core::types::{Agent, TaskId};
+use meddler_mcp::{
+    GetPromptResult, Prompt, PromptArgument, PromptContent, PromptError, PromptMessage,
+    ResourceUri, Role,
+};
+
+use crate::app_state::AppState;
+use crate::mcp_session;
+use crate::resources;
+
+const FAN_OUT: &str = "fan_out";
+const REVIEW_PLAN: &str = "review_plan";
+const SUMMARISE_TASK: &str = "summarise_task";
+
+/// Reviewer the `review_plan` prompt asks when none is given.
+const DEFAULT_REVIEWER: &str = "scrutinizer";
+
+/// The orchestration prompts, for `prompts/list`. Descriptions name the
+/// agents currently registered, so the user can pick one without looking it
+/// up first.
+///
+/// # Errors
+///
+/// Returns an error if agents cannot be loaded.
+pub async fn list(state: &AppState) -> Result<Vec<Prompt>, PromptError> {
+    let agents = worker_names(&workers(state).await?);
+    let connected = worker_names(&connected_workers(state).await?);
+
+    Ok(vec![
+        Prompt {
+            name: FAN_OUT.to_string(),
+            description: Some(format!(
+                "Ask every connected agent the same question and compare their answers. \
+                 Connected now: {connected}."
+            )),
+            arguments: vec![
+                argument("question", "The question to ask every agent", true),
+                argument("task_id", "Existing task to group the answers under", false),
+            ],
+        },
+        Prompt {
+            name: REVIEW_PLAN.to_string(),
+            description: Some(
+                "Have a reviewer agent scrutinise a plan before acting on it.".to_string(),
+            ),
+            arguments: vec![
+                argument("plan", "The plan to review", true),
+                argument(
+                    "reviewer",
+                    &format!(
+                        "Agent to review the plan (default: {DEFAULT_REVIEWER}). \
+                         Registered: {agents}"
+                    ),
+                    false,
+                ),
+            ],
+        },
+        Prompt {
+            name: SUMMARISE_TASK.to_string(),
+            description: Some(
+                "Summarise a task from its transcript: contributions, outcome and open questions."
+                    .to_string(),
+            ),
+            arguments: vec![argument("task_id", "The task to summarise", true)],
+        },
+    ])
+}
+
+/// Render a prompt, for `prompts/get`.
+///
+/// # Errors
+///
+/// Returns [`PromptError::UnknownPrompt`] for names not in [`list`], an
+/// argument error if a required argument is missing or names something that
+/// doesn't exist, or an error if the data behind the prompt cannot be loaded.
+pub async fn get(
+    state: &AppState,
+    name: &str,
+    arguments: &BTreeMap<String, String>,
+) -> Result<GetPromptResult, PromptError> {
+    match name {
+        FAN_OUT => fan_out(state, arguments).await,
+        REVIEW_PLAN => review_plan(state, arguments).await,
+        SUMMARISE_TASK => summarise_task(state, arguments).await,
+        _ => Err(PromptError::UnknownPrompt(name.to_string())),
+    }
+}
+
+async fn fan_out(
+    state: &AppState,
+    arguments: &BTreeMap<String, String>,
+) -> Result<GetPromptResult, PromptError> {
+    let question = required(arguments, "question")?;
+    let agents = connected_workers(state).await?;
+
+    let task_step = match arguments.get("task_id") {
+        Some(task_id) => format!("Use the existing task {task_id} to group the answers."),
+        None => "Create a task for the question with create_task, so the answers are grouped."
+            .to_string(),
+    };
+    let text = format!(
+        "Fan this question out to every connected agent: {}.\n\n\
+         1. {task_step}\n\
+         2. Ask them all at once with fan_out, passing the task_id; it waits for their \
+         replies.\n\
+         3. Compare the replies: where they agree, where they differ, and which answer is best \
+         supported.\n\n\
+         Question:\n{question}",
+        worker_names(&agents)
+    );
+
+    Ok(GetPromptResult {
+        description: Some(format!("Fan out a question to {} agents", agents.len())),
+        messages: vec![PromptMessage::user(text)],
+    })
+}
+
+async fn review_plan(
+    state: &AppState,
+    arguments: &BTreeMap<String, String>,
+) -> Result<GetPromptResult, PromptError> {
+    let plan = required(arguments, "plan")?;
+    let reviewer = arguments
+        .get("reviewer")
+        .map_or(DEFAULT_REVIEWER, String::as_str);
+    let reviewer = state.agent_registry.get_by_name(reviewer).await?;
+
+    let text = format!(
+        "Before acting on the plan below, send it to {} with send_and_wait and ask them to \
+         scrutinise it: missing steps, risky assumptions, and anything that should be done \
+         differently. Then revise the plan based on their review, and show me the revised plan \
+         with what changed and why.\n\nPlan:\n{plan}",
+        reviewer.name
+    );
+
+    Ok(GetPromptResult {
+        description: Some(format!("Review a plan with {}", reviewer.name)),
+        messages: vec![PromptMessage::user(text)],
+    })
+}
+
+async fn summarise_task(
+    state: &AppState,
+    arguments: &BTreeMap<String, String>,
+) -> Result<GetPromptResult, PromptError> {
+    let task_id = required(arguments, "task_id")?
+        .parse::<uuid::Uuid>()
+        .map(TaskId)
+        .map_err(|e| PromptError::InvalidArgument(format!("task_id: {e}")))?;
+    let transcript = resources::read(state, &ResourceUri::TaskTranscript(task_id)).await?;
+
+    Ok(GetPromptResult {
+        description: Some(format!("Summarise task {task_id}")),
+        messages: vec![
+            PromptMessage {
+                role: Role::User,
+                content: PromptContent::Resource {
+                    resource: transcript,
+                },
+            },
+            PromptMessage::user(
+                "Summarise the task transcript above: what was asked, what each agent \
+                 contributed, the outcome, and any open questions or disagreements.",
+            ),
+        ],
+    })
+}
+
+fn argument(name: &str, description: &str, required: bool) -> PromptArgument {
+    PromptArgument {
+        name: name.to_string(),
+        description: Some(description.to_string()),
+        required,
+    }
+}
+
+fn required<'a>(
+    arguments: &'a BTreeMap<String, String>,
+    name: &str,
+) -> Result<&'a str, PromptError> {
+    arguments
+        .get(name)
+        .map(String::as_str)
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| PromptError::MissingArgument(name.to_string()))
+}
+
+/// Every registered agent other than the orchestrators and the system agent.
+async fn workers(state: &AppState) -> Result<Vec<Agent>, PromptError> {
+    let mut agents = state.agent_registry.list().await?;
+    agents.retain(|agent| mcp_session::is_worker(&agent.name));
+    Ok(agents)
+}
+
+/// The workers with an open inbox.
+async fn connected_workers(state: &AppState) -> Result<Vec<Agent>, PromptError> {
+    let mut connected = Vec::new();
+    for agent in workers(state).await? {
+        if state.sessions.is_connected(&agent.name).await {
+            connected.push(agent);
+        }
+    }
+    Ok(connected)
+}
+
+fn worker_names(agents: &[Agent]) -> String {
+    if agents.is_empty() {
+        return "none".to_string();
+    }
+    agents
+        .iter()
+        .map(|agent| agent.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
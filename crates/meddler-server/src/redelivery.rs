@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use meddler_core::error::Error;
+
+use crate::app_state::AppState;
+use crate::delivery;
+
+/// Settings for redelivering messages that were never acknowledged.
+#[derive(Debug, Clone, Copy)]
+pub struct RedeliveryConfig {
+    /// How long a delivered message may stay unacknowledged before it is
+    /// handed out again.
+    pub visibility_timeout: Duration,
+    /// Give up redelivering a message after this many delivery attempts.
+    pub max_attempts: i32,
+}
+
+impl RedeliveryConfig {
+    /// Read the config from `MEDDLER_ACK_TIMEOUT_SECS` (default 300) and
+    /// `MEDDLER_MAX_DELIVERY_ATTEMPTS` (default 5). A timeout of 0 disables
+    /// redelivery.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let timeout_secs = env_or("MEDDLER_ACK_TIMEOUT_SECS", 300);
+        if timeout_secs == 0 {
+            return None;
+        }
+        Some(Self {
+            visibility_timeout: Duration::from_secs(timeout_secs),
+            max_attempts: env_or("MEDDLER_MAX_DELIVERY_ATTEMPTS", 5),
+        })
+    }
+}
+
+pub(crate) fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Spawn the background loop that periodically redelivers expired messages.
+pub fn spawn(state: AppState, config: RedeliveryConfig) -> JoinHandle<()> {
+    let period = (config.visibility_timeout / 2).max(Duration::from_secs(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            match redeliver_expired(&state, config).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Redelivered {n} unacknowledged messages"),
+                Err(e) => tracing::error!("Redelivery sweep failed: {e}"),
+            }
+        }
+    })
+}
+
+/// Requeue every message whose visibility timeout has expired and push it to
+/// its recipient again. Recipients that are offline get it from their pending
+/// queue when they reconnect. Returns how many messages were requeued.
+///
+/// # Errors
+///
+/// Returns an error if the expired messages cannot be requeued.
+pub async fn redeliver_expired(state: &AppState, config: RedeliveryConfig) -> Result<usize, Error> {
+    let timeout = chrono::Duration::from_std(config.visibility_timeout)
+        .map_err(|e| Error::Internal(e.to_string()))?;
+    let expired = state
+        .message_store
+        .requeue_unacknowledged(chrono::Utc::now() - timeout, config.max_attempts)
+        .await?;
+
+    let count = expired.len();
+    for message in expired {
+        match state.agent_registry.get_by_id(message.recipient_id).await {
+            Ok(recipient) => {
+                delivery::deliver(state, &recipient.name, message).await;
+            }
+            Err(e) => tracing::warn!("Cannot redeliver message {}: {e}", message.id),
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use meddler_core::error::Error;
+use meddler_core::types::{AgentId, MessageFilter, TaskId};
+use meddler_mcp::{Resource, ResourceContents, ResourceUri};
+
+use crate::app_state::AppState;
+use crate::delivery::Delivery;
+use crate::mcp_session;
+
+/// Every agent and task as MCP resources, for `resources/list`. Each task is
+/// listed along with its transcript.
+///
+/// # Errors
+///
+/// Returns an error if agents or tasks cannot be loaded.
+pub async fn list(state: &AppState) -> Result<Vec<Resource>, Error> {
+    let mut resources = Vec::new();
+
+    for agent in state.agent_registry.list().await? {
+        if !mcp_session::is_worker(&agent.name) {
+            continue;
+        }
+        let uri = ResourceUri::Agent(agent.name.clone());
+        resources.push(Resource {
+            uri: uri.to_string(),
+            mime_type: Some(uri.mime_type().to_string()),
+            name: format!("Agent: {}", agent.name),
+            description: Some(agent.description),
+        });
+    }
+
+    for task in state.task_store.list().await? {
+        for uri in [ResourceUri::Task(task.id), ResourceUri::TaskTranscript(task.id)] {
+            let name = match uri {
+                ResourceUri::TaskTranscript(_) => format!("Transcript: {}", task.title),
+                _ => format!("Task: {}", task.title),
+            };
+            resources.push(Resource {
+                uri: uri.to_string(),
+                mime_type: Some(uri.mime_type().to_string()),
+                name,
+                description: None,
+            });
+        }
+    }
+
+    Ok(resources)
+}
+
+/// Read a resource, for `resources/read`.
+///
+/// # Errors
+///
+/// Returns [`Error::AgentNotFound`] or [`Error::TaskNotFound`] if the resource
+/// doesn't exist, or an error if it cannot be loaded.
+pub async fn read(state: &AppState, uri: &ResourceUri) -> Result<ResourceContents, Error> {
+    let text = match uri {
+        ResourceUri::Agent(name) => {
+            let agent = state.agent_registry.get_by_name(name).await?;
+            let connected = state.sessions.is_connected(&agent.name).await;
+            to_json(&serde_json::json!({
+                "name": agent.name,
+                "description": agent.description,
+                "connected": connected,
+                "registered_at": agent.registered_at,
+                "last_seen_at": agent.last_seen_at,
+            }))?
+        }
+        ResourceUri::AgentInbox(name) => to_json(&inbox(state, name).await?)?,
+        ResourceUri::Task(id) => to_json(&state.task_store.get_status(*id).await?)?,
+        ResourceUri::TaskTranscript(id) => transcript(state, *id).await?,
+    };
+
+    Ok(ResourceContents {
+        uri: uri.to_string(),
+        mime_type: uri.mime_type().to_string(),
+        text,
+    })
+}
+
+/// Every message waiting for an orchestrator, oldest first. Reading leaves
+/// them in place; the orchestrator acknowledges them with
+/// `acknowledge_messages` once it has dealt with them.
+async fn inbox(state: &AppState, name: &str) -> Result<Vec<Delivery>, Error> {
+    if !mcp_session::is_orchestrator(name) {
+        return Err(Error::AgentNotFound(name.to_string()));
+    }
+    let orchestrator = state.agent_registry.get_by_name(name).await?;
+    let waiting = state.message_store.unacknowledged_for(orchestrator.id).await?;
+
+    let mut names: HashMap<AgentId, String> = HashMap::new();
+    let mut inbox = Vec::with_capacity(waiting.len());
+    for message in waiting {
+        let sender_name = agent_name(state, &mut names, message.sender_id).await;
+        inbox.push(Delivery {
+            message,
+            sender_name,
+        });
+    }
+    Ok(inbox)
+}
+
+/// Render a task's messages as a plain-text conversation.
+async fn transcript(state: &AppState, task_id: TaskId) -> Result<String, Error> {
+    let task = state.task_store.get(task_id).await?;
+    let messages = state
+        .message_store
+        .query(MessageFilter {
+            task_id: Some(task_id),
+            ..MessageFilter::default()
+        })
+        .await?;
+
+    let mut names: HashMap<AgentId, String> = HashMap::new();
+    let mut text = format!("# {}\n", task.title);
+    for message in messages {
+        let from = agent_name(state, &mut names, message.sender_id).await;
+        let to = agent_name(state, &mut names, message.recipient_id).await;
+        let _ = write!(
+            text,
+            "\n[{}] {from} -> {to}:\n{}\n",
+            message.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            message.content
+        );
+    }
+    Ok(text)
+}
+
+/// Resolve (and cache) an agent's name, falling back to its id.
+async fn agent_name(state: &AppState, names: &mut HashMap<AgentId, String>, id: AgentId) -> String {
+    if let Some(name) = names.get(&id) {
+        return name.clone();
+    }
+    let name = state
+        .agent_registry
+        .get_by_id(id)
+        .await
+        .map_or_else(|_| id.to_string(), |agent| agent.name);
+    names.insert(id, name.clone());
+    name
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, Error> {
+    serde_json::to_string_pretty(value).map_err(|e| Error::Internal(e.to_string()))
+}
@@ -13,13 +13,20 @@ pub fn create_router(state: AppState) -> Router {
         // Health check
         .route("/health", get(handlers::health))
         // MCP endpoints (for orchestrator via Cursor/Claude Desktop)
-        // Supports both Streamable HTTP (POST) and legacy SSE (GET) on the same URL.
+        // `/mcp/sse` supports both Streamable HTTP (POST) and legacy SSE (GET);
+        // `/mcp` is the Streamable HTTP endpoint with session streams (GET/DELETE).
         .route("/mcp/sse", get(handlers::mcp_sse).post(handlers::mcp_request))
-        .route("/mcp", post(handlers::mcp_request))
+        .route(
+            "/mcp",
+            get(handlers::mcp_stream)
+                .post(handlers::mcp_request)
+                .delete(handlers::mcp_delete),
+        )
         // Agent endpoints (for worker agents via CLI)
         .route("/agent/register", post(handlers::agent_register))
         .route("/agent/sse/{name}", get(handlers::agent_sse))
         .route("/agent/message", post(handlers::agent_message))
+        .route("/agent/ack", post(handlers::agent_ack))
         // CORS: allow any origin (MCP clients like Cursor may run in various contexts)
         .layer(CorsLayer::permissive())
         .with_state(state)
@@ -1,14 +1,18 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, oneshot, Mutex, RwLock};
 
-use meddler_core::types::Message;
+use meddler_core::types::{AgentId, Message, MessageId};
 
-/// Manages active SSE sessions for connected agents.
+/// Manages active SSE sessions for connected agents, and in-process waiters
+/// blocked on a reply to a specific message.
 pub struct SessionManager {
     /// Map of agent name -> broadcast sender for SSE notifications.
     sessions: RwLock<HashMap<String, broadcast::Sender<Arc<Message>>>>,
+    /// Map of message id -> the agent it was sent to, and a waiter for that
+    /// agent's first reply to it.
+    reply_waiters: Mutex<HashMap<MessageId, (AgentId, oneshot::Sender<Message>)>>,
 }
 
 impl SessionManager {
@@ -16,6 +20,7 @@ impl SessionManager {
     pub fn new() -> Self {
         Self {
             sessions: RwLock::new(HashMap::new()),
+            reply_waiters: Mutex::new(HashMap::new()),
         }
     }
 
@@ -46,10 +51,60 @@ impl SessionManager {
         sessions.remove(agent_name);
     }
 
-    /// Check if an agent has an active session.
+    /// Wait for the first reply to a message from `recipient`, the agent it
+    /// was sent to. Register before the message is dispatched, so the reply
+    /// cannot arrive unobserved.
+    ///
+    /// Dropping the receiver gives up the wait; abandoned waiters (e.g. of a
+    /// cancelled tool call) are cleared out the next time one is registered.
+    pub async fn await_reply(
+        &self,
+        id: MessageId,
+        recipient: AgentId,
+    ) -> oneshot::Receiver<Message> {
+        let (tx, rx) = oneshot::channel();
+        let mut waiters = self.reply_waiters.lock().await;
+        waiters.retain(|_, (_, waiter)| !waiter.is_closed());
+        waiters.insert(id, (recipient, tx));
+        rx
+    }
+
+    /// Hand a reply to whoever is waiting on the message it answers. Replies
+    /// from anyone but the message's recipient are ignored.
+    pub async fn resolve_reply(&self, reply: &Message) {
+        let Some(original) = reply.in_reply_to else {
+            return;
+        };
+        let mut waiters = self.reply_waiters.lock().await;
+        if !waiters
+            .get(&original)
+            .is_some_and(|(recipient, _)| *recipient == reply.sender_id)
+        {
+            return;
+        }
+        if let Some((_, waiter)) = waiters.remove(&original) {
+            let _ = waiter.send(reply.clone());
+        }
+    }
+
+    /// How many replies are still being waited for.
+    pub async fn pending_replies(&self) -> usize {
+        let mut waiters = self.reply_waiters.lock().await;
+        waiters.retain(|_, (_, waiter)| !waiter.is_closed());
+        waiters.len()
+    }
+
+    /// Stop waiting for a reply (e.g. after a timeout).
+    pub async fn cancel_reply(&self, id: MessageId) {
+        self.reply_waiters.lock().await.remove(&id);
+    }
+
+    /// Check if an agent has an active session with at least one listener.
     pub async fn is_connected(&self, agent_name: &str) -> bool {
         let sessions = self.sessions.read().await;
-        sessions.contains_key(agent_name)
+        sessions
+            .get(agent_name)
+            .is_some_and(|sender| sender.receiver_count() > 0)
     }
 }
 
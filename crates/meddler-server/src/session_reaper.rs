@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::app_state::AppState;
+use crate::redelivery::env_or;
+
+/// Settings for ending MCP sessions whose clients went away without a DELETE.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionReaperConfig {
+    /// How long a session may go without requests, running calls or an open
+    /// stream before it is ended.
+    pub idle_timeout: Duration,
+}
+
+impl SessionReaperConfig {
+    /// Read the config from `MEDDLER_MCP_SESSION_IDLE_SECS` (default 1800). A
+    /// timeout of 0 keeps sessions until they are deleted.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let idle_secs = env_or("MEDDLER_MCP_SESSION_IDLE_SECS", 1800);
+        if idle_secs == 0 {
+            return None;
+        }
+        Some(Self {
+            idle_timeout: Duration::from_secs(idle_secs),
+        })
+    }
+}
+
+/// Spawn the background loop that periodically ends idle MCP sessions.
+pub fn spawn(state: AppState, config: SessionReaperConfig) -> JoinHandle<()> {
+    let period = (config.idle_timeout / 2).max(Duration::from_secs(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            let evicted = state.mcp_sessions.evict_idle(config.idle_timeout).await;
+            if evicted > 0 {
+                tracing::info!("Ended {evicted} idle MCP sessions");
+            }
+        }
+    })
+}
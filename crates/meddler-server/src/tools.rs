@@ -0,0 +1,1147 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use meddler_core::types::{
+    Agent, CreateMessage, CreateTask, Message, MessageFilter, MessageId, MessageOrder, Task,
+    TaskAssignment, TaskId, TaskRole, TaskState, TaskStatus,
+};
+use meddler_mcp::tools::output_schema;
+use meddler_mcp::{ProtocolVersion, Tool, ToolAnnotations, ToolError, ToolRegistry};
+
+use crate::app_state::AppState;
+use crate::delivery;
+use crate::mcp_session::{self, McpSession};
+
+/// Default and maximum time `send_and_wait` blocks for a reply.
+const DEFAULT_WAIT_SECS: u64 = 120;
+const MAX_WAIT_SECS: u64 = 600;
+
+/// Default and maximum page size of `get_messages`.
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
+/// What a tool knows about the MCP request it is serving.
+#[derive(Clone)]
+pub struct ToolContext {
+    pub state: AppState,
+    /// The client's session, if it has one.
+    pub session: Option<Arc<McpSession>>,
+    /// Agent name the client acts as.
+    pub orchestrator: String,
+    /// Protocol revision the client speaks.
+    pub protocol: ProtocolVersion,
+    /// The `_meta.progressToken` of the call, if the client wants progress
+    /// notifications for it.
+    pub progress_token: Option<Value>,
+    /// The request's own SSE response stream, if the POST was upgraded.
+    pub(crate) stream: Option<mpsc::UnboundedSender<Value>>,
+}
+
+impl ToolContext {
+    /// Send a notification related to this request: on its SSE response
+    /// stream if it has one, otherwise on the session's GET stream.
+    pub fn notify(&self, method: &str, params: Value) {
+        if let Some(stream) = &self.stream {
+            let _ = stream.send(mcp_session::notification(method, params));
+        } else if let Some(session) = &self.session {
+            session.notify(method, params);
+        }
+    }
+
+    /// Report how far the call has got, if the client asked for progress.
+    /// `message` is dropped for clients on revisions that predate it.
+    pub fn progress(&self, progress: usize, total: Option<usize>, message: &str) {
+        let Some(token) = &self.progress_token else {
+            return;
+        };
+        let mut params = serde_json::json!({
+            "progressToken": token,
+            "progress": progress,
+        });
+        if let Some(total) = total {
+            params["total"] = total.into();
+        }
+        if self.protocol.supports_progress_message() {
+            params["message"] = message.into();
+        }
+        self.notify("notifications/progress", params);
+    }
+
+    /// The orchestrator agent the request acts as.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the orchestrator is not registered.
+    pub async fn orchestrator_agent(&self) -> Result<Agent, ToolError> {
+        Ok(self
+            .state
+            .agent_registry
+            .get_by_name(&self.orchestrator)
+            .await?)
+    }
+}
+
+/// The tools meddler ships with. Servers embedding meddler can register
+/// their own tools on top before putting the registry into [`AppState`].
+#[must_use]
+pub fn builtin() -> ToolRegistry<ToolContext> {
+    let mut tools = ToolRegistry::new();
+    tools
+        .register(ListAgentsTool)
+        .register(SendMessageTool)
+        .register(SendAndWaitTool)
+        .register(FanOutTool)
+        .register(GetMessagesTool)
+        .register(SearchMessagesTool)
+        .register(AcknowledgeMessagesTool)
+        .register(CreateTaskTool)
+        .register(GetTaskStatusTool)
+        .register(ListSubtasksTool)
+        .register(AssignTaskTool)
+        .register(UnassignTaskTool)
+        .register(CompleteTaskTool)
+        .register(CancelTaskTool);
+    tools
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListAgentsArgs {}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ListAgentsOutput {
+    pub agents: Vec<AgentSummary>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AgentSummary {
+    pub name: String,
+    pub description: String,
+    /// Whether the agent has an open inbox and will get messages right away
+    pub connected: bool,
+}
+
+pub struct ListAgentsTool;
+
+#[async_trait]
+impl Tool<ToolContext> for ListAgentsTool {
+    type Args = ListAgentsArgs;
+
+    fn name(&self) -> &'static str {
+        "list_agents"
+    }
+
+    fn description(&self) -> &'static str {
+        "List all registered agents and their descriptions."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::read_only())
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(output_schema::<ListAgentsOutput>())
+    }
+
+    async fn call(&self, ctx: &ToolContext, _args: ListAgentsArgs) -> Result<Value, ToolError> {
+        let agents = ctx.state.agent_registry.list().await?;
+
+        // Filter out the internal orchestrator and system agents
+        let mut agent_list = Vec::new();
+        for a in agents {
+            if !mcp_session::is_worker(&a.name) {
+                continue;
+            }
+            let connected = ctx.state.sessions.is_connected(&a.name).await;
+            agent_list.push(AgentSummary {
+                name: a.name,
+                description: a.description,
+                connected,
+            });
+        }
+
+        Ok(serde_json::json!(ListAgentsOutput { agents: agent_list }))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SendMessageArgs {
+    /// Name of the recipient agent
+    pub to: String,
+    /// Message content to send
+    pub content: String,
+    /// Optional task ID to group related messages
+    pub task_id: Option<uuid::Uuid>,
+}
+
+pub struct SendMessageTool;
+
+#[async_trait]
+impl Tool<ToolContext> for SendMessageTool {
+    type Args = SendMessageArgs;
+
+    fn name(&self) -> &'static str {
+        "send_message"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send a message to a specific agent by name. Returns the message ID without waiting for a response; use send_and_wait to block until the agent replies."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::additive())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: SendMessageArgs) -> Result<Value, ToolError> {
+        let (_, recipient, message) = store_orchestrator_message(ctx, args).await?;
+
+        // Push to recipient's SSE (queued as pending if offline)
+        let (message_id, seq) = (message.id, message.seq);
+        let delivered = delivery::dispatch(&ctx.state, &recipient.name, message).await;
+
+        Ok(serde_json::json!({
+            "message_id": message_id,
+            "seq": seq,
+            "delivered": delivered,
+        }))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SendAndWaitArgs {
+    #[serde(flatten)]
+    pub message: SendMessageArgs,
+    /// How long to wait for the reply in seconds (default 120, max 600)
+    pub timeout_secs: Option<u64>,
+}
+
+pub struct SendAndWaitTool;
+
+#[async_trait]
+impl Tool<ToolContext> for SendAndWaitTool {
+    type Args = SendAndWaitArgs;
+
+    fn name(&self) -> &'static str {
+        "send_and_wait"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send a message to a specific agent by name and wait for its reply, which is returned as the result. Gives up after the timeout."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::additive())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: SendAndWaitArgs) -> Result<Value, ToolError> {
+        let state = &ctx.state;
+        let timeout_secs = args
+            .timeout_secs
+            .unwrap_or(DEFAULT_WAIT_SECS)
+            .min(MAX_WAIT_SECS);
+
+        let (sender, recipient, message) = store_orchestrator_message(ctx, args.message).await?;
+        let message_id = message.id;
+
+        // Start waiting before dispatch so a fast reply can't be missed
+        let reply = state.sessions.await_reply(message_id, recipient.id).await;
+        let delivered = delivery::dispatch(state, &recipient.name, message).await;
+        ctx.notify(
+            "notifications/message",
+            serde_json::json!({
+                "level": "info",
+                "logger": "meddler",
+                "data": if delivered {
+                    format!("Waiting up to {timeout_secs}s for '{}' to reply", recipient.name)
+                } else {
+                    format!("'{}' is offline; message queued, waiting up to {timeout_secs}s for a reply", recipient.name)
+                },
+            }),
+        );
+        ctx.progress(0, Some(1), &format!("Waiting for '{}' to reply", recipient.name));
+
+        let Ok(Ok(reply)) = tokio::time::timeout(Duration::from_secs(timeout_secs), reply).await
+        else {
+            state.sessions.cancel_reply(message_id).await;
+            return Ok(serde_json::json!({
+                "message_id": message_id,
+                "delivered": delivered,
+                "timed_out": true,
+                "reply": null,
+            }));
+        };
+
+        // The reply reached the orchestrator as this tool's result
+        if let Err(e) = state.message_store.acknowledge(reply.id, sender.id).await {
+            tracing::warn!("Failed to acknowledge reply {}: {e}", reply.id);
+        }
+        ctx.progress(1, Some(1), &format!("'{}' replied", recipient.name));
+
+        Ok(serde_json::json!({
+            "message_id": message_id,
+            "delivered": delivered,
+            "timed_out": false,
+            "reply": {
+                "message_id": reply.id,
+                "from": recipient.name,
+                "content": reply.content,
+                "created_at": reply.created_at,
+            },
+        }))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FanOutArgs {
+    /// Names of the agents to ask (default: every connected agent)
+    pub to: Option<Vec<String>>,
+    /// Message content to send to each of them
+    pub content: String,
+    /// Optional task ID to group the question and answers
+    pub task_id: Option<uuid::Uuid>,
+    /// How long to wait for the replies in seconds (default 120, max 600)
+    pub timeout_secs: Option<u64>,
+}
+
+pub struct FanOutTool;
+
+#[async_trait]
+impl Tool<ToolContext> for FanOutTool {
+    type Args = FanOutArgs;
+
+    fn name(&self) -> &'static str {
+        "fan_out"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send the same message to several agents (by default every connected one) and wait for all of their replies, which are returned together. Agents that don't reply before the timeout are listed as timed out."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::additive())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: FanOutArgs) -> Result<Value, ToolError> {
+        let state = &ctx.state;
+        let timeout_secs = args
+            .timeout_secs
+            .unwrap_or(DEFAULT_WAIT_SECS)
+            .min(MAX_WAIT_SECS);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout_secs);
+
+        let names = match args.to {
+            Some(names) => names,
+            None => connected_workers(state).await?,
+        };
+        let total = names.len();
+
+        // Resolve every recipient and check the task before sending anything,
+        // so a bad name can't fail the call after some agents were asked
+        let sender = ctx.orchestrator_agent().await?;
+        let mut recipients = Vec::with_capacity(total);
+        for name in &names {
+            recipients.push(state.agent_registry.get_by_name(name).await?);
+        }
+        let task_id = args.task_id.map(TaskId);
+        if let Some(tid) = task_id {
+            state.task_store.mark_started(tid).await?;
+        }
+
+        // Start waiting on every message before dispatching any of them
+        let mut waiting = tokio::task::JoinSet::new();
+        let mut pending = HashMap::new();
+        for recipient in recipients {
+            let message = state
+                .message_store
+                .create(CreateMessage {
+                    sender_id: sender.id,
+                    recipient_id: recipient.id,
+                    task_id,
+                    in_reply_to: None,
+                    content: args.content.clone(),
+                })
+                .await?;
+            let message_id = message.id;
+            let reply = state.sessions.await_reply(message_id, recipient.id).await;
+            delivery::dispatch(state, &recipient.name, message).await;
+            waiting.spawn(async move { (message_id, reply.await) });
+            pending.insert(message_id, recipient);
+        }
+        ctx.progress(0, Some(total), &format!("0/{total} agents responded"));
+
+        let mut replies = Vec::new();
+        while let Ok(Some(joined)) = tokio::time::timeout_at(deadline, waiting.join_next()).await
+        {
+            let Ok((message_id, Ok(reply))) = joined else {
+                continue;
+            };
+            let Some(recipient) = pending.remove(&message_id) else {
+                continue;
+            };
+            if let Err(e) = state.message_store.acknowledge(reply.id, sender.id).await {
+                tracing::warn!("Failed to acknowledge reply {}: {e}", reply.id);
+            }
+            replies.push(serde_json::json!({
+                "message_id": reply.id,
+                "from": recipient.name,
+                "content": reply.content,
+                "created_at": reply.created_at,
+            }));
+            let done = replies.len();
+            ctx.progress(done, Some(total), &format!("{done}/{total} agents responded"));
+        }
+
+        let mut timed_out = Vec::new();
+        for (message_id, recipient) in pending {
+            state.sessions.cancel_reply(message_id).await;
+            timed_out.push(recipient.name);
+        }
+        timed_out.sort();
+
+        Ok(serde_json::json!({
+            "replies": replies,
+            "timed_out": timed_out,
+        }))
+    }
+}
+
+/// Names of the worker agents with an open inbox.
+async fn connected_workers(state: &AppState) -> Result<Vec<String>, ToolError> {
+    let mut names = Vec::new();
+    for agent in state.agent_registry.list().await? {
+        if mcp_session::is_worker(&agent.name)
+            && state.sessions.is_connected(&agent.name).await
+        {
+            names.push(agent.name);
+        }
+    }
+    Ok(names)
+}
+
+/// Store a message from the request's orchestrator. Returns the sender,
+/// recipient and message.
+async fn store_orchestrator_message(
+    ctx: &ToolContext,
+    args: SendMessageArgs,
+) -> Result<(Agent, Agent, Message), ToolError> {
+    let state = &ctx.state;
+    let task_id = args.task_id.map(TaskId);
+
+    // Resolve orchestrator as sender
+    let sender = ctx.orchestrator_agent().await?;
+
+    // Resolve recipient
+    let recipient = state.agent_registry.get_by_name(&args.to).await?;
+
+    // If there's a task, mark it as started; closed tasks take no messages
+    if let Some(tid) = task_id {
+        state.task_store.mark_started(tid).await?;
+    }
+
+    let message = state
+        .message_store
+        .create(CreateMessage {
+            sender_id: sender.id,
+            recipient_id: recipient.id,
+            task_id,
+            in_reply_to: None,
+            content: args.content,
+        })
+        .await?;
+
+    Ok((sender, recipient, message))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetMessagesArgs {
+    #[serde(flatten)]
+    pub filters: MessageFilterArgs,
+    /// Full-text search over message content
+    pub query: Option<String>,
+    #[serde(flatten)]
+    pub page: PageArgs,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchMessagesArgs {
+    /// Words to search for. Supports "quoted phrases", or, and -excluded words
+    pub query: String,
+    #[serde(flatten)]
+    pub filters: MessageFilterArgs,
+    #[serde(flatten)]
+    pub page: PageArgs,
+}
+
+/// Filters shared by the message history tools.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MessageFilterArgs {
+    /// Filter by task ID
+    pub task_id: Option<uuid::Uuid>,
+    /// Filter by sender agent name
+    pub sender: Option<String>,
+    /// Filter by recipient agent name
+    pub recipient: Option<String>,
+    /// Two agent names: only messages between them, in either direction
+    pub between: Option<[String; 2]>,
+    /// Only messages sent at or after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Only messages sent before this time (RFC 3339)
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PageArgs {
+    /// Maximum number of messages to return (default 50, max 200)
+    pub limit: Option<u32>,
+    /// Only messages after this seq (the previous page's `next_cursor` when oldest first)
+    pub after: Option<i64>,
+    /// Only messages before this seq (the previous page's `next_cursor` when newest first)
+    pub before: Option<i64>,
+    /// Which messages come first
+    pub order: Option<Order>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    OldestFirst,
+    NewestFirst,
+}
+
+impl From<Order> for MessageOrder {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::OldestFirst => Self::OldestFirst,
+            Order::NewestFirst => Self::NewestFirst,
+        }
+    }
+}
+
+pub struct GetMessagesTool;
+
+#[async_trait]
+impl Tool<ToolContext> for GetMessagesTool {
+    type Args = GetMessagesArgs;
+
+    fn name(&self) -> &'static str {
+        "get_messages"
+    }
+
+    fn description(&self) -> &'static str {
+        "Retrieve message history with optional filters, oldest first unless ordered otherwise, one page at a time. If more messages match, the result's next_cursor is set; pass it as 'after' (or 'before' when reading newest_first) to get the next page."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::read_only())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: GetMessagesArgs) -> Result<Value, ToolError> {
+        query_messages(ctx, args.filters, args.query, args.page, MessageOrder::OldestFirst).await
+    }
+}
+
+pub struct SearchMessagesTool;
+
+#[async_trait]
+impl Tool<ToolContext> for SearchMessagesTool {
+    type Args = SearchMessagesArgs;
+
+    fn name(&self) -> &'static str {
+        "search_messages"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search message content for words or phrases, newest first unless ordered otherwise. Accepts the same filters and paging as get_messages."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::read_only())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: SearchMessagesArgs) -> Result<Value, ToolError> {
+        if args.query.trim().is_empty() {
+            return Err(ToolError::InvalidArguments("query must not be empty".into()));
+        }
+        let query = Some(args.query);
+        query_messages(ctx, args.filters, query, args.page, MessageOrder::NewestFirst).await
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AcknowledgeMessagesArgs {
+    /// IDs of messages from your inbox that you have dealt with
+    pub message_ids: Vec<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AcknowledgeMessagesOutput {
+    /// How many messages were acknowledged
+    pub acknowledged: usize,
+}
+
+pub struct AcknowledgeMessagesTool;
+
+#[async_trait]
+impl Tool<ToolContext> for AcknowledgeMessagesTool {
+    type Args = AcknowledgeMessagesArgs;
+
+    fn name(&self) -> &'static str {
+        "acknowledge_messages"
+    }
+
+    fn description(&self) -> &'static str {
+        "Mark messages from your inbox resource as handled, so they no longer show up in it. Either every message is acknowledged or, if one isn't addressed to you, none is."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::idempotent())
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(output_schema::<AcknowledgeMessagesOutput>())
+    }
+
+    async fn call(
+        &self,
+        ctx: &ToolContext,
+        args: AcknowledgeMessagesArgs,
+    ) -> Result<Value, ToolError> {
+        let orchestrator = ctx.orchestrator_agent().await?;
+        let store = &ctx.state.message_store;
+
+        // Check every id first so a typo doesn't leave the inbox half-acknowledged
+        let ids: Vec<MessageId> = args.message_ids.into_iter().map(MessageId).collect();
+        for &id in &ids {
+            let message = store.get_message(id).await?;
+            if message.recipient_id != orchestrator.id {
+                return Err(ToolError::MessageNotFound(id.to_string()));
+            }
+        }
+        for &id in &ids {
+            store.acknowledge(id, orchestrator.id).await?;
+        }
+
+        Ok(serde_json::json!(AcknowledgeMessagesOutput {
+            acknowledged: ids.len(),
+        }))
+    }
+}
+
+/// Run a message history query and return one page of it.
+async fn query_messages(
+    ctx: &ToolContext,
+    filters: MessageFilterArgs,
+    text: Option<String>,
+    page: PageArgs,
+    default_order: MessageOrder,
+) -> Result<Value, ToolError> {
+    let registry = &ctx.state.agent_registry;
+
+    let sender_id = match &filters.sender {
+        Some(name) => Some(registry.get_by_name(name).await?.id),
+        None => None,
+    };
+
+    let recipient_id = match &filters.recipient {
+        Some(name) => Some(registry.get_by_name(name).await?.id),
+        None => None,
+    };
+
+    let between = match &filters.between {
+        Some([a, b]) => Some((
+            registry.get_by_name(a).await?.id,
+            registry.get_by_name(b).await?.id,
+        )),
+        None => None,
+    };
+
+    let limit = page
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    // Fetch one extra message to find out whether there is another page
+    let mut messages = ctx
+        .state
+        .message_store
+        .query(MessageFilter {
+            task_id: filters.task_id.map(TaskId),
+            sender_id,
+            recipient_id,
+            in_reply_to: None,
+            between,
+            since: filters.since,
+            until: filters.until,
+            text: text.filter(|text| !text.trim().is_empty()),
+            after_seq: page.after,
+            before_seq: page.before,
+            limit: Some(limit + 1),
+            order: page.order.map_or(default_order, MessageOrder::from),
+        })
+        .await?;
+    let next_cursor = if messages.len() > limit as usize {
+        messages.truncate(limit as usize);
+        messages.last().map(|m| m.seq)
+    } else {
+        None
+    };
+
+    Ok(serde_json::json!({
+        "messages": messages,
+        "next_cursor": next_cursor,
+    }))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateTaskArgs {
+    /// Title of the task
+    pub title: String,
+    /// Optional time budget in seconds (e.g., 28800 for 8 hours). Subtasks
+    /// default to, and can't exceed, the parent's remaining time
+    pub time_budget_secs: Option<i64>,
+    /// Create this task as a step of an existing task
+    pub parent_task_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CreateTaskOutput {
+    pub task_id: uuid::Uuid,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_task_id: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_budget_secs: Option<i64>,
+}
+
+pub struct CreateTaskTool;
+
+#[async_trait]
+impl Tool<ToolContext> for CreateTaskTool {
+    type Args = CreateTaskArgs;
+
+    fn name(&self) -> &'static str {
+        "create_task"
+    }
+
+    fn description(&self) -> &'static str {
+        "Create a new task to group related messages. Optionally set a time budget in seconds, or make it a subtask of another task."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::additive())
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(output_schema::<CreateTaskOutput>())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: CreateTaskArgs) -> Result<Value, ToolError> {
+        // Resolve orchestrator as creator
+        let creator = ctx.orchestrator_agent().await?;
+
+        let task = ctx
+            .state
+            .task_store
+            .create(CreateTask {
+                title: args.title,
+                created_by: creator.id,
+                parent_task_id: args.parent_task_id.map(TaskId),
+                time_budget_secs: args.time_budget_secs,
+            })
+            .await?;
+
+        Ok(serde_json::json!(CreateTaskOutput {
+            task_id: task.id.0,
+            title: task.title,
+            parent_task_id: task.parent_task_id.map(|t| t.0),
+            time_budget_secs: task.time_budget_secs,
+        }))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetTaskStatusArgs {
+    /// The task ID to check
+    pub task_id: uuid::Uuid,
+}
+
+pub struct GetTaskStatusTool;
+
+#[async_trait]
+impl Tool<ToolContext> for GetTaskStatusTool {
+    type Args = GetTaskStatusArgs;
+
+    fn name(&self) -> &'static str {
+        "get_task_status"
+    }
+
+    fn description(&self) -> &'static str {
+        "Get the status of a task, including elapsed and remaining time, its assigned agents' activity (messages sent and received, last active, connected), and its subtasks as a tree. Totals roll up elapsed time and message counts over the whole tree."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::read_only())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: GetTaskStatusArgs) -> Result<Value, ToolError> {
+        let task = ctx.state.task_store.get(TaskId(args.task_id)).await?;
+        let tree = task_tree(&ctx.state, task, Utc::now()).await?;
+
+        Ok(serde_json::json!(tree))
+    }
+}
+
+/// A task's status with its subtasks, as reported by `get_task_status`.
+#[derive(Debug, Serialize)]
+pub struct TaskTree {
+    #[serde(flatten)]
+    pub status: TaskStatus,
+    /// Messages sent under this task itself.
+    pub message_count: u64,
+    /// Elapsed time summed over this task and all of its subtasks.
+    pub total_elapsed_secs: i64,
+    /// Messages sent under this task and all of its subtasks.
+    pub total_message_count: u64,
+    /// The agents assigned to this task and what they have done on it.
+    pub assignees: Vec<AssigneeStatus>,
+    pub subtasks: Vec<TaskTree>,
+}
+
+/// An assigned agent's participation in a task.
+#[derive(Debug, Serialize)]
+pub struct AssigneeStatus {
+    pub agent: String,
+    pub role: TaskRole,
+    pub assigned_at: DateTime<Utc>,
+    /// Whether the agent is online right now.
+    pub connected: bool,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    /// When the agent last sent a message under the task.
+    pub last_active_at: Option<DateTime<Utc>>,
+}
+
+async fn task_tree(
+    state: &AppState,
+    task: Task,
+    now: DateTime<Utc>,
+) -> Result<TaskTree, ToolError> {
+    let message_count = state
+        .message_store
+        .count(MessageFilter {
+            task_id: Some(task.id),
+            ..MessageFilter::default()
+        })
+        .await?;
+
+    let mut assignees = Vec::new();
+    for assignment in state.task_store.list_assignments(task.id).await? {
+        assignees.push(assignee_status(state, assignment).await?);
+    }
+
+    let mut subtasks = Vec::new();
+    for subtask in state.task_store.list_subtasks(task.id).await? {
+        subtasks.push(Box::pin(task_tree(state, subtask, now)).await?);
+    }
+
+    let status = TaskStatus::compute(task, now);
+    let total_elapsed_secs = status.elapsed_secs.unwrap_or_default()
+        + subtasks.iter().map(|t| t.total_elapsed_secs).sum::<i64>();
+    let total_message_count =
+        message_count + subtasks.iter().map(|t| t.total_message_count).sum::<u64>();
+
+    Ok(TaskTree {
+        status,
+        message_count,
+        total_elapsed_secs,
+        total_message_count,
+        assignees,
+        subtasks,
+    })
+}
+
+async fn assignee_status(
+    state: &AppState,
+    assignment: TaskAssignment,
+) -> Result<AssigneeStatus, ToolError> {
+    let agent = state.agent_registry.get_by_id(assignment.agent_id).await?;
+    let sent = MessageFilter {
+        task_id: Some(assignment.task_id),
+        sender_id: Some(agent.id),
+        ..MessageFilter::default()
+    };
+    let received = MessageFilter {
+        task_id: Some(assignment.task_id),
+        recipient_id: Some(agent.id),
+        ..MessageFilter::default()
+    };
+    let last_sent = state
+        .message_store
+        .query(MessageFilter {
+            limit: Some(1),
+            order: MessageOrder::NewestFirst,
+            ..sent.clone()
+        })
+        .await?;
+
+    Ok(AssigneeStatus {
+        connected: is_connected(state, &agent.name).await,
+        messages_sent: state.message_store.count(sent).await?,
+        messages_received: state.message_store.count(received).await?,
+        last_active_at: last_sent.first().map(|m| m.created_at),
+        role: assignment.role,
+        assigned_at: assignment.assigned_at,
+        agent: agent.name,
+    })
+}
+
+/// Whether an agent is online: an open inbox for workers, a live MCP session
+/// for orchestrators.
+async fn is_connected(state: &AppState, name: &str) -> bool {
+    if mcp_session::is_orchestrator(name) {
+        state.mcp_sessions.has_orchestrator(name).await
+    } else {
+        state.sessions.is_connected(name).await
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ListSubtasksArgs {
+    /// The parent task ID
+    pub task_id: uuid::Uuid,
+}
+
+pub struct ListSubtasksTool;
+
+#[async_trait]
+impl Tool<ToolContext> for ListSubtasksTool {
+    type Args = ListSubtasksArgs;
+
+    fn name(&self) -> &'static str {
+        "list_subtasks"
+    }
+
+    fn description(&self) -> &'static str {
+        "List the direct subtasks of a task, oldest first, with their state and elapsed and remaining time."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::read_only())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: ListSubtasksArgs) -> Result<Value, ToolError> {
+        let parent = TaskId(args.task_id);
+        // Fail on unknown tasks rather than reporting no subtasks
+        ctx.state.task_store.get(parent).await?;
+
+        let now = Utc::now();
+        let subtasks: Vec<TaskStatus> = ctx
+            .state
+            .task_store
+            .list_subtasks(parent)
+            .await?
+            .into_iter()
+            .map(|task| TaskStatus::compute(task, now))
+            .collect();
+
+        Ok(serde_json::json!({ "subtasks": subtasks }))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CompleteTaskArgs {
+    /// The task ID to complete
+    pub task_id: uuid::Uuid,
+    /// Summary of the outcome: what was achieved, or why it failed
+    pub outcome: String,
+    /// Whether the task achieved its goal (default true); false marks it failed
+    pub succeeded: Option<bool>,
+}
+
+pub struct CompleteTaskTool;
+
+#[async_trait]
+impl Tool<ToolContext> for CompleteTaskTool {
+    type Args = CompleteTaskArgs;
+
+    fn name(&self) -> &'static str {
+        "complete_task"
+    }
+
+    fn description(&self) -> &'static str {
+        "Close a task as completed (or failed, with succeeded: false), recording a summary of the outcome. Closed tasks accept no new messages."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::destructive())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: CompleteTaskArgs) -> Result<Value, ToolError> {
+        let state = if args.succeeded.unwrap_or(true) {
+            TaskState::Completed
+        } else {
+            TaskState::Failed
+        };
+        close_task(ctx, args.task_id, state, Some(args.outcome)).await
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CancelTaskArgs {
+    /// The task ID to cancel
+    pub task_id: uuid::Uuid,
+    /// Why the task is being abandoned
+    pub reason: Option<String>,
+}
+
+pub struct CancelTaskTool;
+
+#[async_trait]
+impl Tool<ToolContext> for CancelTaskTool {
+    type Args = CancelTaskArgs;
+
+    fn name(&self) -> &'static str {
+        "cancel_task"
+    }
+
+    fn description(&self) -> &'static str {
+        "Cancel a task that is no longer needed. Cancelled tasks accept no new messages."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations::destructive())
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: CancelTaskArgs) -> Result<Value, ToolError> {
+        close_task(ctx, args.task_id, TaskState::Cancelled, args.reason).await
+    }
+}
+
+async fn close_task(
+    ctx: &ToolContext,
+    task_id: uuid::Uuid,
+    state: TaskState,
+    outcome: Option<String>,
+) -> Result<Value, ToolError> {
+    let task = ctx
+        .state
+        .task_store
+        .transition(TaskId(task_id), state, outcome)
+        .await?;
+    let status = TaskStatus::compute(task, Utc::now());
+
+    Ok(serde_json::json!(status))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AssignTaskArgs {
+    /// The task ID to assign
+    pub task_id: uuid::Uuid,
+    /// Name of the agent to assign
+    pub agent: String,
+    /// The agent's role on the task (default contributor)
+    pub role: Option<Role>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Contributor,
+    Reviewer,
+}
+
+impl From<Role> for TaskRole {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::Owner => Self::Owner,
+            Role::Contributor => Self::Contributor,
+            Role::Reviewer => Self::Reviewer,
+        }
+    }
+}
+
+pub struct AssignTaskTool;
+
+#[async_trait]
+impl Tool<ToolContext> for AssignTaskTool {
+    type Args = AssignTaskArgs;
+
+    fn name(&self) -> &'static str {
+        "assign_task"
+    }
+
+    fn description(&self) -> &'static str {
+        "Assign an agent to a task as its owner, a contributor or a reviewer. Assigning an agent that is already on the task changes its role."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations {
+            idempotent_hint: Some(true),
+            ..ToolAnnotations::additive()
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: AssignTaskArgs) -> Result<Value, ToolError> {
+        let agent = ctx.state.agent_registry.get_by_name(&args.agent).await?;
+        let role = args.role.map_or(TaskRole::Contributor, TaskRole::from);
+        let assignment = ctx
+            .state
+            .task_store
+            .assign(TaskId(args.task_id), agent.id, role)
+            .await?;
+
+        Ok(serde_json::json!({
+            "task_id": assignment.task_id,
+            "agent": agent.name,
+            "role": assignment.role,
+            "assigned_at": assignment.assigned_at,
+        }))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UnassignTaskArgs {
+    /// The task ID to remove the agent from
+    pub task_id: uuid::Uuid,
+    /// Name of the agent to remove
+    pub agent: String,
+}
+
+pub struct UnassignTaskTool;
+
+#[async_trait]
+impl Tool<ToolContext> for UnassignTaskTool {
+    type Args = UnassignTaskArgs;
+
+    fn name(&self) -> &'static str {
+        "unassign_task"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove an agent from a task. Its messages under the task are kept."
+    }
+
+    fn annotations(&self) -> Option<ToolAnnotations> {
+        Some(ToolAnnotations {
+            idempotent_hint: Some(true),
+            ..ToolAnnotations::destructive()
+        })
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: UnassignTaskArgs) -> Result<Value, ToolError> {
+        let task_id = TaskId(args.task_id);
+        ctx.state.task_store.get(task_id).await?;
+        let agent = ctx.state.agent_registry.get_by_name(&args.agent).await?;
+        let unassigned = ctx.state.task_store.unassign(task_id, agent.id).await?;
+
+        Ok(serde_json::json!({ "unassigned": unassigned }))
+    }
+}
@@ -1,26 +1,69 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum_test::TestServer;
+use tokio_stream::StreamExt;
+
+use meddler_core::types::{
+    CreateMessage, CreateTask, DeliveryState, MessageFilter, TaskRole, TaskState, SYSTEM_AGENT,
+};
+use meddler_server::app_state::AppState;
+use meddler_server::budget::{BudgetConfig, BudgetEvent, BudgetWatcher};
+use meddler_server::delivery;
+use meddler_server::redelivery::{self, RedeliveryConfig};
+use meddler_server::tools::ToolContext;
 
 mod mock_stores;
 use mock_stores::{MockAgentRegistry, MockMessageStore, MockTaskStore};
 
-fn build_test_app() -> TestServer {
+fn build_test_state() -> AppState {
     let agent_registry = Arc::new(MockAgentRegistry::new());
     let message_store = Arc::new(MockMessageStore::new());
     let task_store = Arc::new(MockTaskStore::new());
 
-    let state = meddler_server::app_state::AppState {
+    AppState {
         agent_registry,
         message_store,
         task_store,
         sessions: Arc::new(meddler_server::session::SessionManager::new()),
-    };
+        mcp_sessions: Arc::new(meddler_server::mcp_session::McpSessionManager::new()),
+        tools: Arc::new(meddler_server::tools::builtin()),
+    }
+}
 
+fn build_test_server(state: AppState) -> TestServer {
     let app = meddler_server::router::create_router(state);
     TestServer::new(app).unwrap()
 }
 
+fn build_test_app() -> TestServer {
+    build_test_server(build_test_state())
+}
+
+async fn register(server: &TestServer, name: &str) {
+    server
+        .post("/agent/register")
+        .json(&serde_json::json!({
+            "name": name,
+            "description": format!("{name} agent")
+        }))
+        .await
+        .assert_status_ok();
+}
+
+async fn send(server: &TestServer, from: &str, to: &str, content: &str) -> serde_json::Value {
+    let resp = server
+        .post("/agent/message")
+        .json(&serde_json::json!({
+            "from": from,
+            "to": to,
+            "content": content
+        }))
+        .await;
+    resp.assert_status_ok();
+    resp.json()
+}
+
 #[tokio::test]
 async fn health_check() {
     let server = build_test_app();
@@ -137,6 +180,25 @@ async fn send_message_unknown_recipient_returns_404() {
     resp.assert_status_not_found();
 }
 
+#[tokio::test]
+async fn reply_to_unknown_message_returns_404() {
+    let server = build_test_app();
+    register(&server, "researcher").await;
+    register(&server, "writer").await;
+
+    let resp = server
+        .post("/agent/message")
+        .json(&serde_json::json!({
+            "from": "researcher",
+            "to": "writer",
+            "content": "As requested",
+            "in_reply_to": uuid::Uuid::new_v4(),
+        }))
+        .await;
+
+    resp.assert_status_not_found();
+}
+
 #[tokio::test]
 async fn mcp_initialize() {
     let server = build_test_app();
@@ -157,27 +219,73 @@ async fn mcp_initialize() {
 }
 
 #[tokio::test]
-async fn mcp_tools_list() {
+async fn mcp_initialize_negotiates_protocol_version() {
+    let server = build_test_app();
+
+    for (requested, negotiated) in [
+        ("2024-11-05", "2024-11-05"),
+        ("2025-03-26", "2025-03-26"),
+        ("2025-06-18", "2025-06-18"),
+        ("2099-01-01", "2025-06-18"),
+    ] {
+        let resp = server
+            .post("/mcp")
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {"protocolVersion": requested}
+            }))
+            .await;
+        let body: serde_json::Value = resp.json();
+        assert_eq!(body["result"]["protocolVersion"], negotiated);
+    }
+}
+
+#[tokio::test]
+async fn mcp_rejects_unsupported_protocol_version_header() {
+    let server = build_test_app();
+    server
+        .post("/mcp")
+        .add_header("mcp-protocol-version", "1999-01-01")
+        .json(&serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+        .await
+        .assert_status_bad_request();
+}
+
+#[tokio::test]
+async fn mcp_batch_request() {
     let server = build_test_app();
 
     let resp = server
         .post("/mcp")
-        .json(&serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "tools/list",
-            "params": {}
-        }))
+        .add_header("mcp-protocol-version", "2025-03-26")
+        .json(&serde_json::json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "tools/list"},
+            {"jsonrpc": "2.0", "method": "notifications/progress"},
+            {"jsonrpc": "2.0", "id": 2, "method": "nonexistent/method"}
+        ]))
         .await;
 
     resp.assert_status_ok();
     let body: serde_json::Value = resp.json();
-    let tools = body["result"]["tools"].as_array().unwrap();
-    assert_eq!(tools.len(), 5);
+    let responses = body.as_array().unwrap();
+    assert_eq!(responses.len(), 2);
+    let tools_list = responses.iter().find(|r| r["id"] == 1).unwrap();
+    assert!(tools_list["result"]["tools"].is_array());
+    let unknown = responses.iter().find(|r| r["id"] == 2).unwrap();
+    assert_eq!(unknown["error"]["code"], -32601);
+
+    // Nothing to answer for a batch of notifications
+    server
+        .post("/mcp")
+        .json(&serde_json::json!([{"jsonrpc": "2.0", "method": "notifications/progress"}]))
+        .await
+        .assert_status(axum::http::StatusCode::ACCEPTED);
 }
 
 #[tokio::test]
-async fn mcp_unknown_method() {
+async fn mcp_batch_rejected_when_protocol_disallows_it() {
     let server = build_test_app();
 
     let resp = server
@@ -185,49 +293,2087 @@ async fn mcp_unknown_method() {
         .json(&serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
-            "method": "nonexistent/method",
-            "params": {}
+            "method": "initialize",
+            "params": {"protocolVersion": "2025-06-18"}
         }))
         .await;
+    let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
 
-    resp.assert_status_ok();
+    let resp = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&serde_json::json!([{"jsonrpc": "2.0", "id": 2, "method": "tools/list"}]))
+        .await;
     let body: serde_json::Value = resp.json();
-    assert!(body.get("error").is_some());
-    assert_eq!(body["error"]["code"], -32601);
+    assert_eq!(body["error"]["code"], -32600);
 }
 
 #[tokio::test]
-async fn mcp_streamable_http_initialize() {
+async fn mcp_malformed_body_returns_jsonrpc_error() {
     let server = build_test_app();
 
-    // POST to /mcp/sse (Streamable HTTP transport) should work the same as /mcp
     let resp = server
-        .post("/mcp/sse")
+        .post("/mcp")
+        .content_type("application/json")
+        .text("{not json")
+        .await;
+    resp.assert_status_bad_request();
+    let body: serde_json::Value = resp.json();
+    assert_eq!(body["error"]["code"], -32700);
+    assert!(body["id"].is_null());
+    assert!(body["error"]["data"]["reason"].is_string());
+
+    let resp = server
+        .post("/mcp")
+        .json(&serde_json::json!({"jsonrpc": "1.0", "id": 3, "method": "tools/list"}))
+        .await;
+    resp.assert_status_bad_request();
+    let body: serde_json::Value = resp.json();
+    assert_eq!(body["error"]["code"], -32600);
+    assert_eq!(body["id"], 3);
+}
+
+#[tokio::test]
+async fn mcp_tools_list() {
+    let server = build_test_app();
+
+    let resp = server
+        .post("/mcp")
         .json(&serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
-            "method": "initialize",
+            "method": "tools/list",
             "params": {}
         }))
         .await;
 
     resp.assert_status_ok();
     let body: serde_json::Value = resp.json();
-    assert_eq!(body["result"]["serverInfo"]["name"], "meddler");
+    let tools = body["result"]["tools"].as_array().unwrap();
+    assert_eq!(tools.len(), 14);
 }
 
 #[tokio::test]
-async fn mcp_notification_returns_accepted() {
+async fn mcp_tool_errors() {
     let server = build_test_app();
+    let call = |name: &str, arguments: serde_json::Value| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": name, "arguments": arguments}
+        })
+    };
 
-    // Notifications have null id and should return 202 Accepted
+    // A failing tool is a result the model can read...
     let resp = server
-        .post("/mcp/sse")
+        .post("/mcp")
+        .json(&call("send_message", serde_json::json!({"to": "nobody", "content": "hi"})))
+        .await;
+    resp.assert_status_ok();
+    let body: serde_json::Value = resp.json();
+    assert!(body.get("error").is_none());
+    assert_eq!(body["result"]["isError"], true);
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let explanation: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(explanation["error"], "agent_not_found");
+
+    // ...while malformed calls are protocol errors
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&call("nonexistent_tool", serde_json::json!({})))
+        .await
+        .json();
+    assert_eq!(body["error"]["code"], -32602);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&call("send_message", serde_json::json!({"to": "nobody"})))
+        .await
+        .json();
+    assert_eq!(body["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn mcp_tool_annotations_and_structured_output() {
+    let server = build_test_app();
+    register(&server, "researcher").await;
+
+    let list = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+    let call = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {"name": "list_agents", "arguments": {}}
+    });
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-protocol-version", "2025-06-18")
+        .json(&list)
+        .await
+        .json();
+    let tools = body["result"]["tools"].as_array().unwrap();
+    let tool = |name: &str| tools.iter().find(|t| t["name"] == name).unwrap().clone();
+    for name in [
+        "list_agents",
+        "get_messages",
+        "search_messages",
+        "get_task_status",
+        "list_subtasks",
+    ] {
+        assert_eq!(tool(name)["annotations"]["readOnlyHint"], true, "{name}");
+    }
+    assert_eq!(tool("send_message")["annotations"]["readOnlyHint"], false);
+    assert_eq!(tool("send_message")["annotations"]["destructiveHint"], false);
+    assert_eq!(
+        tool("list_agents")["outputSchema"]["properties"]["agents"]["type"],
+        "array"
+    );
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-protocol-version", "2025-06-18")
+        .json(&call)
+        .await
+        .json();
+    let result = &body["result"];
+    assert_eq!(result["structuredContent"]["agents"][0]["name"], "researcher");
+    let text: serde_json::Value =
+        serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(text, result["structuredContent"]);
+
+    // Older clients get neither
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-protocol-version", "2024-11-05")
+        .json(&list)
+        .await
+        .json();
+    let tools = body["result"]["tools"].as_array().unwrap();
+    assert!(tools.iter().all(|t| t.get("annotations").is_none()));
+    assert!(tools.iter().all(|t| t.get("outputSchema").is_none()));
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-protocol-version", "2024-11-05")
+        .json(&call)
+        .await
+        .json();
+    assert!(body["result"].get("structuredContent").is_none());
+}
+
+/// Check a value against the subset of JSON Schema that tool output schemas
+/// use: `type`, `properties`, `required` and `items`.
+fn assert_matches_schema(value: &serde_json::Value, schema: &serde_json::Value, path: &str) {
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            serde_json::Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            t => vec![t.as_str().unwrap()],
+        };
+        let matches = |t: &&str| match *t {
+            "null" => value.is_null(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "string" => value.is_string(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => false,
+        };
+        assert!(types.iter().any(matches), "{path}: {value} is not {types:?}");
+    }
+    if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+        for field in required {
+            let field = field.as_str().unwrap();
+            assert!(value.get(field).is_some(), "{path}: missing {field}");
+        }
+    }
+    if let (Some(properties), Some(object)) = (
+        schema.get("properties").and_then(|p| p.as_object()),
+        value.as_object(),
+    ) {
+        for (field, value) in object {
+            if let Some(schema) = properties.get(field) {
+                assert_matches_schema(value, schema, &format!("{path}.{field}"));
+            }
+        }
+    }
+    if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+        for (i, value) in values.iter().enumerate() {
+            assert_matches_schema(value, items, &format!("{path}[{i}]"));
+        }
+    }
+}
+
+#[tokio::test]
+async fn mcp_structured_content_matches_output_schema() {
+    let server = build_test_app();
+    register(&server, "researcher").await;
+
+    let rpc = |method: &str, params: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params})
+    };
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-protocol-version", "2025-06-18")
+        .json(&rpc("tools/list", serde_json::json!({})))
+        .await
+        .json();
+    let tools = body["result"]["tools"].as_array().unwrap().clone();
+    let call = |name: &'static str, arguments: serde_json::Value| {
+        let server = &server;
+        let tools = &tools;
+        async move {
+            let schema = &tools.iter().find(|t| t["name"] == name).unwrap()["outputSchema"];
+            let body: serde_json::Value = server
+                .post("/mcp")
+                .add_header("mcp-protocol-version", "2025-06-18")
+                .json(&rpc(
+                    "tools/call",
+                    serde_json::json!({"name": name, "arguments": arguments}),
+                ))
+                .await
+                .json();
+            let structured = body["result"]["structuredContent"].clone();
+            assert!(schema.is_object(), "{name} has no output schema");
+            assert_matches_schema(&structured, schema, name);
+            structured
+        }
+    };
+
+    call("list_agents", serde_json::json!({})).await;
+    let parent = call("create_task", serde_json::json!({"title": "Survey"})).await;
+    assert!(parent.get("parent_task_id").is_none());
+    let subtask = call(
+        "create_task",
+        serde_json::json!({
+            "title": "Find sources",
+            "parent_task_id": parent["task_id"],
+            "time_budget_secs": 60
+        }),
+    )
+    .await;
+    assert_eq!(subtask["parent_task_id"], parent["task_id"]);
+}
+
+#[tokio::test]
+async fn mcp_tool_schemas_are_generated_from_args() {
+    let server = build_test_app();
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+        .await
+        .json();
+    let tools = body["result"]["tools"].as_array().unwrap();
+    let schema = |name: &str| {
+        tools.iter().find(|t| t["name"] == name).unwrap()["inputSchema"].clone()
+    };
+
+    let send = schema("send_and_wait");
+    assert_eq!(send["type"], "object");
+    assert_eq!(send["required"], serde_json::json!(["to", "content"]));
+    assert_eq!(send["properties"]["timeout_secs"]["type"], "integer");
+    assert_eq!(
+        send["properties"]["to"]["description"],
+        "Name of the recipient agent"
+    );
+
+    let search = schema("search_messages");
+    assert_eq!(search["required"], serde_json::json!(["query"]));
+    assert_eq!(search["properties"]["between"]["type"], "array");
+    assert_eq!(search["properties"]["since"]["format"], "date-time");
+
+    let list = schema("list_agents");
+    assert_eq!(list["properties"], serde_json::json!({}));
+}
+
+/// A tool an embedding server adds on top of the built-in ones.
+struct WhoAmI;
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+struct WhoAmIArgs {
+    /// Greeting to prefix the name with
+    greeting: String,
+}
+
+#[async_trait::async_trait]
+impl meddler_mcp::Tool<ToolContext> for WhoAmI {
+    type Args = WhoAmIArgs;
+
+    fn name(&self) -> &'static str {
+        "whoami"
+    }
+
+    fn description(&self) -> &'static str {
+        "Say which orchestrator is calling."
+    }
+
+    async fn call(
+        &self,
+        ctx: &ToolContext,
+        args: WhoAmIArgs,
+    ) -> Result<serde_json::Value, meddler_mcp::ToolError> {
+        Ok(serde_json::json!(format!("{} {}", args.greeting, ctx.orchestrator)))
+    }
+}
+
+#[tokio::test]
+async fn mcp_custom_tools_can_be_registered() {
+    let mut tools = meddler_server::tools::builtin();
+    tools.register(WhoAmI);
+    let state = AppState {
+        tools: Arc::new(tools),
+        ..build_test_state()
+    };
+    let server = build_test_server(state);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+        .await
+        .json();
+    assert_eq!(body["result"]["tools"].as_array().unwrap().len(), 15);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
         .json(&serde_json::json!({
             "jsonrpc": "2.0",
-            "method": "notifications/initialized"
+            "id": 2,
+            "method": "tools/call",
+            "params": {"name": "whoami", "arguments": {"greeting": "hello"}}
         }))
-        .await;
+        .await
+        .json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    assert_eq!(text, "\"hello __orchestrator__\"");
+}
 
-    resp.assert_status(axum::http::StatusCode::ACCEPTED);
+#[tokio::test]
+async fn mcp_get_messages_is_paginated() {
+    let server = build_test_app();
+    register(&server, "alice").await;
+    register(&server, "bob").await;
+    for i in 0..5 {
+        send(&server, "alice", "bob", &format!("message {i}")).await;
+    }
+
+    let get_page = |arguments: serde_json::Value| {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": "get_messages", "arguments": arguments}
+        });
+        let request = server.post("/mcp").json(&request);
+        async move {
+            let body: serde_json::Value = request.await.json();
+            let text = body["result"]["content"][0]["text"].as_str().unwrap();
+            let page: serde_json::Value = serde_json::from_str(text).unwrap();
+            let contents: Vec<String> = page["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| m["content"].as_str().unwrap().to_string())
+                .collect();
+            (contents, page["next_cursor"].clone())
+        }
+    };
+
+    let (first, cursor) = get_page(serde_json::json!({"sender": "alice", "limit": 2})).await;
+    assert_eq!(first, ["message 0", "message 1"]);
+    let (second, cursor) =
+        get_page(serde_json::json!({"sender": "alice", "limit": 2, "after": cursor})).await;
+    assert_eq!(second, ["message 2", "message 3"]);
+    let (last, cursor) =
+        get_page(serde_json::json!({"sender": "alice", "limit": 2, "after": cursor})).await;
+    assert_eq!(last, ["message 4"]);
+    assert!(cursor.is_null());
+
+    // Newest first pages backwards with `before`
+    let args = serde_json::json!({"limit": 3, "order": "newest_first"});
+    let (newest, cursor) = get_page(args).await;
+    assert_eq!(newest, ["message 4", "message 3", "message 2"]);
+    let args = serde_json::json!({"limit": 3, "order": "newest_first", "before": cursor});
+    let (older, cursor) = get_page(args).await;
+    assert_eq!(older, ["message 1", "message 0"]);
+    assert!(cursor.is_null());
+}
+
+#[tokio::test]
+async fn mcp_message_filters_and_search() {
+    let server = build_test_app();
+    for name in ["alice", "bob", "carol"] {
+        register(&server, name).await;
+    }
+    send(&server, "alice", "bob", "the deploy failed on staging").await;
+    send(&server, "bob", "alice", "rolling back the deploy").await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let later = chrono::Utc::now();
+    send(&server, "carol", "alice", "deploy looks fine in production").await;
+    send(&server, "alice", "carol", "thanks, closing the incident").await;
+
+    let call = |tool: &str, arguments: serde_json::Value| {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": tool, "arguments": arguments}
+        });
+        let request = server.post("/mcp").json(&request);
+        async move {
+            let body: serde_json::Value = request.await.json();
+            let text = body["result"]["content"][0]["text"].as_str().unwrap();
+            let page: serde_json::Value = serde_json::from_str(text).unwrap();
+            page["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| m["content"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let between = call("get_messages", serde_json::json!({"between": ["bob", "alice"]})).await;
+    assert_eq!(between, ["the deploy failed on staging", "rolling back the deploy"]);
+
+    let since = call("get_messages", serde_json::json!({"since": later})).await;
+    assert_eq!(since.len(), 2);
+    let until = call("get_messages", serde_json::json!({"until": later})).await;
+    assert_eq!(until.len(), 2);
+
+    // Search is newest first and combines with the other filters
+    let found = call("search_messages", serde_json::json!({"query": "deploy"})).await;
+    assert_eq!(
+        found,
+        [
+            "deploy looks fine in production",
+            "rolling back the deploy",
+            "the deploy failed on staging"
+        ]
+    );
+    let found = call(
+        "search_messages",
+        serde_json::json!({"query": "deploy -production", "sender": "alice"}),
+    )
+    .await;
+    assert_eq!(found, ["the deploy failed on staging"]);
+    let found = call("get_messages", serde_json::json!({"query": "incident"})).await;
+    assert_eq!(found, ["thanks, closing the incident"]);
+}
+
+#[tokio::test]
+async fn mcp_task_lifecycle() {
+    let server = build_test_app();
+    register(&server, "researcher").await;
+
+    let call = |tool: &str, arguments: serde_json::Value| {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": tool, "arguments": arguments}
+        });
+        let request = server.post("/mcp").json(&request);
+        async move {
+            let body: serde_json::Value = request.await.json();
+            let text = body["result"]["content"][0]["text"].as_str().unwrap();
+            let result: serde_json::Value = serde_json::from_str(text).unwrap();
+            (body["result"]["isError"] == true, result)
+        }
+    };
+
+    let (_, created) = call("create_task", serde_json::json!({"title": "Survey"})).await;
+    let task_id = created["task_id"].clone();
+    let (_, status) = call("get_task_status", serde_json::json!({"task_id": task_id})).await;
+    assert_eq!(status["task"]["state"], "pending");
+
+    let message = serde_json::json!({"to": "researcher", "content": "go", "task_id": task_id});
+    let (failed, _) = call("send_message", message.clone()).await;
+    assert!(!failed);
+    let (_, status) = call("get_task_status", serde_json::json!({"task_id": task_id})).await;
+    assert_eq!(status["task"]["state"], "running");
+
+    let (failed, status) = call(
+        "complete_task",
+        serde_json::json!({"task_id": task_id, "outcome": "Found three sources"}),
+    )
+    .await;
+    assert!(!failed);
+    assert_eq!(status["task"]["state"], "completed");
+    assert_eq!(status["task"]["outcome"], "Found three sources");
+    assert!(status["task"]["completed_at"].is_string());
+
+    // Closed tasks take no messages and don't reopen
+    let (failed, error) = call("send_message", message).await;
+    assert!(failed);
+    assert_eq!(error["error"], "invalid_task_state");
+    let (failed, error) = call("cancel_task", serde_json::json!({"task_id": task_id})).await;
+    assert!(failed);
+    assert_eq!(error["error"], "invalid_task_state");
+    server
+        .post("/agent/message")
+        .json(&serde_json::json!({
+            "from": "researcher",
+            "to": "researcher",
+            "content": "late",
+            "task_id": task_id,
+        }))
+        .await
+        .assert_status(axum::http::StatusCode::CONFLICT);
+
+    // Tasks can be cancelled before they start
+    let (_, created) = call("create_task", serde_json::json!({"title": "Unused"})).await;
+    let (failed, status) = call(
+        "cancel_task",
+        serde_json::json!({"task_id": created["task_id"], "reason": "Not needed"}),
+    )
+    .await;
+    assert!(!failed);
+    assert_eq!(status["task"]["state"], "cancelled");
+}
+
+#[tokio::test]
+async fn mcp_subtasks() {
+    let server = build_test_app();
+    register(&server, "researcher").await;
+
+    let call = |tool: &str, arguments: serde_json::Value| {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": tool, "arguments": arguments}
+        });
+        let request = server.post("/mcp").json(&request);
+        async move {
+            let body: serde_json::Value = request.await.json();
+            let text = body["result"]["content"][0]["text"].as_str().unwrap();
+            serde_json::from_str::<serde_json::Value>(text).unwrap()
+        }
+    };
+    let send = |task_id: &serde_json::Value| {
+        call(
+            "send_message",
+            serde_json::json!({"to": "researcher", "content": "go", "task_id": task_id}),
+        )
+    };
+
+    let root = call(
+        "create_task",
+        serde_json::json!({"title": "Write report", "time_budget_secs": 3600}),
+    )
+    .await;
+    let root_id = root["task_id"].clone();
+
+    // Subtasks inherit the parent's budget, and can't ask for more
+    let research = call(
+        "create_task",
+        serde_json::json!({"title": "Research", "parent_task_id": root_id}),
+    )
+    .await;
+    assert_eq!(research["parent_task_id"], root_id);
+    assert_eq!(research["time_budget_secs"], 3600);
+    let error = call(
+        "create_task",
+        serde_json::json!({"title": "Draft", "parent_task_id": root_id, "time_budget_secs": 7200}),
+    )
+    .await;
+    assert_eq!(error["error"], "invalid_task_state");
+    assert!(error["message"].as_str().unwrap().contains("exceeds the 3600s left"));
+    let draft = call(
+        "create_task",
+        serde_json::json!({"title": "Draft", "parent_task_id": root_id, "time_budget_secs": 600}),
+    )
+    .await;
+    let review = call(
+        "create_task",
+        serde_json::json!({"title": "Review", "parent_task_id": draft["task_id"]}),
+    )
+    .await;
+    assert_eq!(review["time_budget_secs"], 600);
+
+    send(&root_id).await;
+    send(&research["task_id"]).await;
+    send(&research["task_id"]).await;
+    send(&review["task_id"]).await;
+
+    let listed = call("list_subtasks", serde_json::json!({"task_id": root_id})).await;
+    let titles: Vec<&str> = listed["subtasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["task"]["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Research", "Draft"]);
+
+    let tree = call("get_task_status", serde_json::json!({"task_id": root_id})).await;
+    assert_eq!(tree["task"]["title"], "Write report");
+    assert_eq!(tree["message_count"], 1);
+    assert_eq!(tree["total_message_count"], 4);
+    assert_eq!(tree["subtasks"][0]["message_count"], 2);
+    assert_eq!(tree["subtasks"][1]["total_message_count"], 1);
+    assert_eq!(tree["subtasks"][1]["subtasks"][0]["task"]["title"], "Review");
+    assert!(tree["total_elapsed_secs"].is_i64());
+}
+
+#[tokio::test]
+async fn mcp_task_assignments() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+    register(&server, "reviewer").await;
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+    let _inbox = delivery::open_inbox(&state, &researcher, None).await.unwrap();
+
+    let call = |tool: &str, arguments: serde_json::Value| {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": tool, "arguments": arguments}
+        });
+        let request = server.post("/mcp").json(&request);
+        async move {
+            let body: serde_json::Value = request.await.json();
+            let text = body["result"]["content"][0]["text"].as_str().unwrap();
+            serde_json::from_str::<serde_json::Value>(text).unwrap()
+        }
+    };
+
+    let task = call("create_task", serde_json::json!({"title": "Survey"})).await;
+    let task_id = task["task_id"].clone();
+    let assigned = call(
+        "assign_task",
+        serde_json::json!({"task_id": task_id, "agent": "researcher"}),
+    )
+    .await;
+    assert_eq!(assigned["role"], "contributor");
+    call(
+        "assign_task",
+        serde_json::json!({"task_id": task_id, "agent": "reviewer", "role": "reviewer"}),
+    )
+    .await;
+    // Assigning again changes the role
+    let assigned = call(
+        "assign_task",
+        serde_json::json!({"task_id": task_id, "agent": "researcher", "role": "owner"}),
+    )
+    .await;
+    assert_eq!(assigned["role"], "owner");
+
+    call(
+        "send_message",
+        serde_json::json!({"to": "researcher", "content": "go", "task_id": task_id}),
+    )
+    .await;
+    let orchestrator = state.agent_registry.list().await.unwrap();
+    let orchestrator = orchestrator
+        .iter()
+        .find(|a| meddler_server::mcp_session::is_orchestrator(&a.name))
+        .unwrap();
+    server
+        .post("/agent/message")
+        .json(&serde_json::json!({
+            "from": "researcher",
+            "to": orchestrator.name,
+            "content": "done",
+            "task_id": task_id,
+        }))
+        .await
+        .assert_status_ok();
+
+    let status = call("get_task_status", serde_json::json!({"task_id": task_id})).await;
+    let assignees = status["assignees"].as_array().unwrap();
+    assert_eq!(assignees.len(), 2);
+    assert_eq!(assignees[0]["agent"], "researcher");
+    assert_eq!(assignees[0]["role"], "owner");
+    assert_eq!(assignees[0]["connected"], true);
+    assert_eq!(assignees[0]["messages_sent"], 1);
+    assert_eq!(assignees[0]["messages_received"], 1);
+    assert!(assignees[0]["last_active_at"].is_string());
+    assert_eq!(assignees[1]["agent"], "reviewer");
+    assert_eq!(assignees[1]["connected"], false);
+    assert_eq!(assignees[1]["messages_sent"], 0);
+    assert!(assignees[1]["last_active_at"].is_null());
+
+    let unassign = serde_json::json!({"task_id": task_id, "agent": "reviewer"});
+    let result = call("unassign_task", unassign.clone()).await;
+    assert_eq!(result["unassigned"], true);
+    let result = call("unassign_task", unassign).await;
+    assert_eq!(result["unassigned"], false);
+
+    // Closed tasks take no new assignees
+    call(
+        "complete_task",
+        serde_json::json!({"task_id": task_id, "outcome": "Done"}),
+    )
+    .await;
+    let error = call(
+        "assign_task",
+        serde_json::json!({"task_id": task_id, "agent": "reviewer"}),
+    )
+    .await;
+    assert_eq!(error["error"], "invalid_task_state");
+}
+
+#[tokio::test]
+async fn mcp_resources() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+
+    let rpc = |id: i64, method: &str, params: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    };
+    let call = |args: serde_json::Value| rpc(1, "tools/call", args);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&call(serde_json::json!({"name": "create_task", "arguments": {"title": "Survey"}})))
+        .await
+        .json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let task_id = serde_json::from_str::<serde_json::Value>(text).unwrap()["task_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    server
+        .post("/mcp")
+        .json(&call(serde_json::json!({
+            "name": "send_message",
+            "arguments": {"to": "researcher", "content": "find sources", "task_id": task_id}
+        })))
+        .await
+        .assert_status_ok();
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&rpc(2, "resources/list", serde_json::json!({})))
+        .await
+        .json();
+    let uris: Vec<&str> = body["result"]["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|r| r["uri"].as_str())
+        .collect();
+    let transcript_uri = format!("meddler://tasks/{task_id}/transcript");
+    assert!(uris.contains(&"meddler://agents/researcher"));
+    assert!(uris.contains(&format!("meddler://tasks/{task_id}").as_str()));
+    assert!(uris.contains(&transcript_uri.as_str()));
+    assert!(!uris.iter().any(|uri| uri.contains("__orchestrator__")));
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&rpc(3, "resources/read", serde_json::json!({"uri": transcript_uri})))
+        .await
+        .json();
+    let contents = &body["result"]["contents"][0];
+    assert_eq!(contents["mimeType"], "text/plain");
+    let text = contents["text"].as_str().unwrap();
+    assert!(text.starts_with("# Survey"));
+    assert!(text.contains("__orchestrator__ -> researcher:\nfind sources"));
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&rpc(4, "resources/read", serde_json::json!({"uri": "meddler://agents/nobody"})))
+        .await
+        .json();
+    assert_eq!(body["error"]["code"], -32002);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&rpc(5, "resources/templates/list", serde_json::json!({})))
+        .await
+        .json();
+    assert_eq!(body["result"]["resourceTemplates"].as_array().unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn mcp_resource_subscriptions() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+
+    let rpc = |id: i64, method: &str, params: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    };
+    let subscribe = |uri: &str| rpc(3, "resources/subscribe", serde_json::json!({"uri": uri}));
+
+    let resp = server
+        .post("/mcp")
+        .json(&rpc(1, "initialize", serde_json::json!({})))
+        .await;
+    let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+    let body: serde_json::Value = resp.json();
+    assert_eq!(body["result"]["capabilities"]["resources"]["subscribe"], true);
+
+    let create_task = serde_json::json!({"name": "create_task", "arguments": {"title": "Survey"}});
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&rpc(2, "tools/call", create_task))
+        .await
+        .json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let task_id = serde_json::from_str::<serde_json::Value>(text).unwrap()["task_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let transcript_uri = format!("meddler://tasks/{task_id}/transcript");
+
+    // Only existing transcripts can be subscribed to, and only within a session
+    let missing = format!("meddler://tasks/{}/transcript", uuid::Uuid::new_v4());
+    for (uri, code) in [(format!("meddler://tasks/{task_id}"), -32602), (missing, -32002)] {
+        let body: serde_json::Value = server
+            .post("/mcp")
+            .add_header("mcp-session-id", session_id.as_str())
+            .json(&subscribe(&uri))
+            .await
+            .json();
+        assert_eq!(body["error"]["code"], code, "{uri}");
+    }
+    let body: serde_json::Value = server.post("/mcp").json(&subscribe(&transcript_uri)).await.json();
+    assert_eq!(body["error"]["code"], -32600);
+
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&subscribe(&transcript_uri))
+        .await
+        .assert_status_ok();
+
+    let session = state.mcp_sessions.get(&session_id).await.unwrap();
+    let mut stream = session.subscribe();
+    let report = |content: &str| {
+        serde_json::json!({
+            "from": "researcher",
+            "to": "__orchestrator__",
+            "content": content,
+            "task_id": task_id,
+        })
+    };
+
+    // A message under the task updates the transcript; one outside it doesn't
+    send(&server, "researcher", "__orchestrator__", "unrelated").await;
+    server
+        .post("/agent/message")
+        .json(&report("found sources"))
+        .await
+        .assert_status_ok();
+
+    let notification = stream.try_recv().unwrap();
+    assert_eq!(notification.message["method"], "notifications/resources/updated");
+    assert_eq!(notification.message["params"]["uri"], transcript_uri);
+    assert!(stream.try_recv().is_err());
+
+    let unsubscribe = rpc(4, "resources/unsubscribe", serde_json::json!({"uri": transcript_uri}));
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&unsubscribe)
+        .await
+        .assert_status_ok();
+    server
+        .post("/agent/message")
+        .json(&report("more sources"))
+        .await
+        .assert_status_ok();
+    assert!(stream.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn mcp_orchestrator_inbox() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+
+    let rpc = |id: i64, method: &str, params: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    };
+    let inbox_uri = "meddler://agents/__orchestrator__:alice/inbox";
+    let connect = || async {
+        let resp = server
+            .post("/mcp")
+            .add_header("x-meddler-orchestrator", "alice")
+            .json(&rpc(1, "initialize", serde_json::json!({})))
+            .await;
+        let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+        let session = state.mcp_sessions.get(&session_id).await.unwrap();
+        let stream = session.subscribe();
+        server
+            .post("/mcp")
+            .add_header("mcp-session-id", session_id.as_str())
+            .json(&rpc(2, "resources/subscribe", serde_json::json!({"uri": inbox_uri})))
+            .await
+            .assert_status_ok();
+        (session_id, stream)
+    };
+
+    // A message outside any task reaches a subscribed orchestrator
+    let (_, mut stream) = connect().await;
+    assert!(stream.try_recv().is_err());
+    send(&server, "researcher", "__orchestrator__:alice", "first").await;
+    let notification = stream.try_recv().unwrap();
+    assert_eq!(notification.message["method"], "notifications/resources/updated");
+    assert_eq!(notification.message["params"]["uri"], inbox_uri);
+
+    // One that arrives while it's away is announced when it subscribes again
+    send(&server, "researcher", "__orchestrator__:alice", "second").await;
+    let (session_id, mut stream) = connect().await;
+    let notification = stream.try_recv().unwrap();
+    assert_eq!(notification.message["params"]["uri"], inbox_uri);
+
+    let read = rpc(3, "resources/read", serde_json::json!({"uri": inbox_uri}));
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&read)
+        .await
+        .json();
+    let text = body["result"]["contents"][0]["text"].as_str().unwrap();
+    let inbox: serde_json::Value = serde_json::from_str(text).unwrap();
+    let inbox = inbox.as_array().unwrap();
+    assert_eq!(inbox.len(), 2);
+    assert_eq!(inbox[0]["content"], "first");
+    assert_eq!(inbox[1]["content"], "second");
+    assert_eq!(inbox[1]["sender_name"], "researcher");
+
+    // Reading leaves them in place until they are acknowledged
+    let reread: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&read)
+        .await
+        .json();
+    assert_eq!(reread, body);
+
+    let acknowledge = |ids: Vec<&serde_json::Value>| {
+        let arguments = serde_json::json!({"message_ids": ids});
+        let params = serde_json::json!({"name": "acknowledge_messages", "arguments": arguments});
+        rpc(4, "tools/call", params)
+    };
+    let unknown = serde_json::json!(uuid::Uuid::new_v4());
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&acknowledge(vec![&inbox[0]["id"], &unknown]))
+        .await
+        .json();
+    assert_eq!(body["result"]["isError"], true);
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&acknowledge(vec![&inbox[0]["id"], &inbox[1]["id"]]))
+        .await
+        .json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(text).unwrap()["acknowledged"], 2);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&read)
+        .await
+        .json();
+    assert_eq!(body["result"]["contents"][0]["text"], "[]");
+    let config = RedeliveryConfig {
+        visibility_timeout: Duration::ZERO,
+        max_attempts: 5,
+    };
+    assert_eq!(redelivery::redeliver_expired(&state, config).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn mcp_orchestrator_inbox_is_private() {
+    let server = build_test_server(build_test_state());
+    let rpc = |id: i64, method: &str, params: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    };
+    server
+        .post("/mcp")
+        .add_header("x-meddler-orchestrator", "alice")
+        .json(&rpc(1, "initialize", serde_json::json!({})))
+        .await
+        .assert_status_ok();
+    let inbox_uri = "meddler://agents/__orchestrator__:alice/inbox";
+
+    // Other orchestrators can't read or subscribe to alice's inbox
+    for method in ["resources/read", "resources/subscribe"] {
+        let resp = server
+            .post("/mcp")
+            .add_header("x-meddler-orchestrator", "bob")
+            .json(&rpc(1, "initialize", serde_json::json!({})))
+            .await;
+        let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+        let body: serde_json::Value = server
+            .post("/mcp")
+            .add_header("mcp-session-id", session_id.as_str())
+            .json(&rpc(2, method, serde_json::json!({"uri": inbox_uri})))
+            .await
+            .json();
+        assert_eq!(body["error"]["code"], -32002, "{method}");
+    }
+}
+
+#[tokio::test]
+async fn mcp_prompts() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+    register(&server, "scrutinizer").await;
+
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+    let _inbox = delivery::open_inbox(&state, &researcher, None).await.unwrap();
+
+    let rpc = |method: &str, params: serde_json::Value| {
+        serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params})
+    };
+    let get = |name: &str, arguments: serde_json::Value| {
+        rpc("prompts/get", serde_json::json!({"name": name, "arguments": arguments}))
+    };
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&rpc("prompts/list", serde_json::json!({})))
+        .await
+        .json();
+    let prompts = body["result"]["prompts"].as_array().unwrap();
+    let names: Vec<&str> = prompts.iter().filter_map(|p| p["name"].as_str()).collect();
+    assert_eq!(names, ["fan_out", "review_plan", "summarise_task"]);
+    // Agent names are filled in live
+    assert!(prompts[0]["description"].as_str().unwrap().ends_with("Connected now: researcher."));
+    assert_eq!(prompts[0]["arguments"][0]["name"], "question");
+    assert_eq!(prompts[0]["arguments"][0]["required"], true);
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&get("fan_out", serde_json::json!({"question": "Is P = NP?"})))
+        .await
+        .json();
+    let message = &body["result"]["messages"][0];
+    assert_eq!(message["role"], "user");
+    let text = message["content"]["text"].as_str().unwrap();
+    assert!(text.contains("every connected agent: researcher."));
+    assert!(text.ends_with("Question:\nIs P = NP?"));
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&get("review_plan", serde_json::json!({"plan": "Ship it"})))
+        .await
+        .json();
+    let text = body["result"]["messages"][0]["content"]["text"].as_str().unwrap();
+    assert!(text.contains("send it to scrutinizer"));
+
+    let create_task = serde_json::json!({"name": "create_task", "arguments": {"title": "Survey"}});
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&rpc("tools/call", create_task))
+        .await
+        .json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let task_id = serde_json::from_str::<serde_json::Value>(text).unwrap()["task_id"].clone();
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&get("summarise_task", serde_json::json!({"task_id": task_id})))
+        .await
+        .json();
+    let messages = body["result"]["messages"].as_array().unwrap();
+    assert_eq!(messages[0]["content"]["type"], "resource");
+    assert!(messages[0]["content"]["resource"]["text"].as_str().unwrap().starts_with("# Survey"));
+    assert_eq!(messages[1]["content"]["type"], "text");
+
+    for request in [
+        get("nope", serde_json::json!({})),
+        get("fan_out", serde_json::json!({})),
+        get("review_plan", serde_json::json!({"plan": "x", "reviewer": "nobody"})),
+        get("summarise_task", serde_json::json!({"task_id": "not-a-uuid"})),
+    ] {
+        let body: serde_json::Value = server.post("/mcp").json(&request).await.json();
+        assert_eq!(body["error"]["code"], -32602, "{request}");
+    }
+}
+
+#[tokio::test]
+async fn mcp_unknown_method() {
+    let server = build_test_app();
+
+    let resp = server
+        .post("/mcp")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "nonexistent/method",
+            "params": {}
+        }))
+        .await;
+
+    resp.assert_status_ok();
+    let body: serde_json::Value = resp.json();
+    assert!(body.get("error").is_some());
+    assert_eq!(body["error"]["code"], -32601);
+}
+
+#[tokio::test]
+async fn mcp_streamable_http_initialize() {
+    let server = build_test_app();
+
+    // POST to /mcp/sse (Streamable HTTP transport) should work the same as /mcp
+    let resp = server
+        .post("/mcp/sse")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {}
+        }))
+        .await;
+
+    resp.assert_status_ok();
+    let body: serde_json::Value = resp.json();
+    assert_eq!(body["result"]["serverInfo"]["name"], "meddler");
+}
+
+#[tokio::test]
+async fn mcp_notification_returns_accepted() {
+    let server = build_test_app();
+
+    // Notifications have null id and should return 202 Accepted
+    let resp = server
+        .post("/mcp/sse")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }))
+        .await;
+
+    resp.assert_status(axum::http::StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn offline_messages_are_queued_and_drained_in_order() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "sender").await;
+    register(&server, "recipient").await;
+
+    // Recipient is offline: both messages stay pending
+    let first = send(&server, "sender", "recipient", "first").await;
+    let second = send(&server, "sender", "recipient", "second").await;
+    assert_eq!(first["delivered"], false);
+    assert_eq!(second["delivered"], false);
+
+    let recipient = state.agent_registry.get_by_name("recipient").await.unwrap();
+    let mut inbox = delivery::open_inbox(&state, &recipient, None).await.unwrap();
+
+    let next = tokio::time::timeout(Duration::from_secs(1), inbox.next());
+    let first = next.await.unwrap().unwrap();
+    assert_eq!(first.message.content, "first");
+    assert_eq!(first.sender_name, "sender");
+    let next = tokio::time::timeout(Duration::from_secs(1), inbox.next());
+    assert_eq!(next.await.unwrap().unwrap().message.content, "second");
+
+    // Once drained, live traffic flows through the same stream
+    let third = send(&server, "sender", "recipient", "third").await;
+    assert_eq!(third["delivered"], true);
+    let next = tokio::time::timeout(Duration::from_secs(1), inbox.next());
+    assert_eq!(next.await.unwrap().unwrap().message.content, "third");
+
+    let messages = state
+        .message_store
+        .query(MessageFilter {
+            recipient_id: Some(recipient.id),
+            ..MessageFilter::default()
+        })
+        .await
+        .unwrap();
+    assert!(messages
+        .iter()
+        .all(|m| m.delivery_state == DeliveryState::Delivered));
+}
+
+#[tokio::test]
+async fn inbox_resumes_after_last_event_id() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "sender").await;
+    register(&server, "recipient").await;
+
+    let recipient = state.agent_registry.get_by_name("recipient").await.unwrap();
+    let mut inbox = delivery::open_inbox(&state, &recipient, None).await.unwrap();
+    send(&server, "sender", "recipient", "seen").await;
+    let seen = tokio::time::timeout(Duration::from_secs(1), inbox.next())
+        .await
+        .unwrap()
+        .unwrap();
+    drop(inbox);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Sent while the connection was down, then already delivered elsewhere
+    send(&server, "sender", "recipient", "missed").await;
+    let mut other = delivery::open_inbox(&state, &recipient, None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(1), other.next())
+        .await
+        .unwrap()
+        .unwrap();
+    drop(other);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    // Resuming from the last seen cursor replays only what came after it
+    let mut resumed = delivery::open_inbox(&state, &recipient, Some(seen.message.seq))
+        .await
+        .unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(1), resumed.next());
+    let replayed = next.await.unwrap().unwrap();
+    assert_eq!(replayed.message.content, "missed");
+    assert!(replayed.message.seq > seen.message.seq);
+}
+
+#[test]
+fn last_event_id_header_is_parsed() {
+    let mut headers = axum::http::HeaderMap::new();
+    assert_eq!(delivery::last_event_id(&headers), None);
+
+    headers.insert("last-event-id", "17".parse().unwrap());
+    assert_eq!(delivery::last_event_id(&headers), Some(17));
+
+    headers.insert("last-event-id", "not-a-cursor".parse().unwrap());
+    assert_eq!(delivery::last_event_id(&headers), None);
+}
+
+#[tokio::test]
+async fn acknowledge_message() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "sender").await;
+    register(&server, "recipient").await;
+    let sent = send(&server, "sender", "recipient", "do the thing").await;
+
+    // Only the recipient may acknowledge
+    server
+        .post("/agent/ack")
+        .json(&serde_json::json!({
+            "agent": "sender",
+            "message_id": sent["message_id"]
+        }))
+        .await
+        .assert_status_not_found();
+
+    let resp = server
+        .post("/agent/ack")
+        .json(&serde_json::json!({
+            "agent": "recipient",
+            "message_id": sent["message_id"]
+        }))
+        .await;
+    resp.assert_status_ok();
+    let body: serde_json::Value = resp.json();
+    assert_eq!(body["delivery_state"], "acknowledged");
+}
+
+#[tokio::test]
+async fn unacknowledged_messages_are_redelivered() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "sender").await;
+    register(&server, "recipient").await;
+    let recipient = state.agent_registry.get_by_name("recipient").await.unwrap();
+    let config = RedeliveryConfig {
+        visibility_timeout: Duration::ZERO,
+        max_attempts: 2,
+    };
+
+    let mut inbox = delivery::open_inbox(&state, &recipient, None)
+        .await
+        .unwrap();
+    let acked = send(&server, "sender", "recipient", "acked").await;
+    send(&server, "sender", "recipient", "dropped").await;
+    for _ in 0..2 {
+        tokio::time::timeout(Duration::from_secs(1), inbox.next())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    server
+        .post("/agent/ack")
+        .json(&serde_json::json!({
+            "agent": "recipient",
+            "message_id": acked["message_id"]
+        }))
+        .await
+        .assert_status_ok();
+
+    // Only the unacknowledged message comes back
+    let requeued = redelivery::redeliver_expired(&state, config).await.unwrap();
+    assert_eq!(requeued, 1);
+    let next = tokio::time::timeout(Duration::from_secs(1), inbox.next());
+    assert_eq!(next.await.unwrap().unwrap().message.content, "dropped");
+
+    // ...until it runs out of attempts
+    let requeued = redelivery::redeliver_expired(&state, config).await.unwrap();
+    assert_eq!(requeued, 0);
+}
+
+#[tokio::test]
+async fn backlog_messages_are_redelivered_after_a_short_ack_timeout() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "sender").await;
+    register(&server, "recipient").await;
+    let recipient = state.agent_registry.get_by_name("recipient").await.unwrap();
+    let config = RedeliveryConfig {
+        visibility_timeout: Duration::ZERO,
+        max_attempts: 5,
+    };
+
+    // Sent while the recipient is offline, so it comes from the backlog
+    send(&server, "sender", "recipient", "queued").await;
+    let mut inbox = delivery::open_inbox(&state, &recipient, None)
+        .await
+        .unwrap();
+    let first = tokio::time::timeout(Duration::from_secs(1), inbox.next());
+    assert_eq!(first.await.unwrap().unwrap().message.content, "queued");
+
+    // Redelivered straight away, it is a new attempt rather than a duplicate
+    let requeued = redelivery::redeliver_expired(&state, config).await.unwrap();
+    assert_eq!(requeued, 1);
+    let again = tokio::time::timeout(Duration::from_secs(1), inbox.next());
+    let again = again.await.unwrap().unwrap().message;
+    assert_eq!(again.content, "queued");
+    assert_eq!(again.delivery_attempts, 1);
+}
+
+#[tokio::test]
+async fn task_budgets_warn_and_time_out() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    let orchestrator_name = meddler_server::mcp_session::orchestrator_name("alice");
+    register(&server, &orchestrator_name).await;
+    register(&server, "researcher").await;
+    let orchestrator = state.agent_registry.get_by_name(&orchestrator_name).await.unwrap();
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+    let session = state
+        .mcp_sessions
+        .create(orchestrator_name, meddler_mcp::ProtocolVersion::LATEST)
+        .await;
+    let mut notifications = session.subscribe();
+
+    let task = state
+        .task_store
+        .create(CreateTask {
+            title: "Survey".to_string(),
+            created_by: orchestrator.id,
+            parent_task_id: None,
+            time_budget_secs: Some(100),
+        })
+        .await
+        .unwrap();
+    state.task_store.mark_started(task.id).await.unwrap();
+    state
+        .message_store
+        .create(CreateMessage {
+            sender_id: orchestrator.id,
+            recipient_id: researcher.id,
+            task_id: Some(task.id),
+            in_reply_to: None,
+            content: "find sources".to_string(),
+        })
+        .await
+        .unwrap();
+    let started = state.task_store.get(task.id).await.unwrap().started_at.unwrap();
+    let at = |secs| started + chrono::Duration::seconds(secs);
+
+    let mut watcher = BudgetWatcher::new(BudgetConfig {
+        check_interval: Duration::from_secs(1),
+        warn_at_percent: vec![50, 80],
+        auto_timeout: true,
+    });
+    assert!(watcher.sweep(&state, at(10)).await.unwrap().is_empty());
+
+    // Only the highest threshold crossed is announced, and only once
+    let events = watcher.sweep(&state, at(85)).await.unwrap();
+    assert_eq!(events, [(task.id, BudgetEvent::Warning(80))]);
+    assert!(watcher.sweep(&state, at(90)).await.unwrap().is_empty());
+    let notification = notifications.try_recv().unwrap();
+    assert_eq!(notification.message["method"], "notifications/message");
+    assert_eq!(notification.message["params"]["data"]["event"], "budget_warning");
+
+    let events = watcher.sweep(&state, at(100)).await.unwrap();
+    assert_eq!(events, [(task.id, BudgetEvent::Expired)]);
+    let notification = notifications.try_recv().unwrap();
+    assert_eq!(notification.message["params"]["data"]["event"], "budget_expired");
+    let task = state.task_store.get(task.id).await.unwrap();
+    assert_eq!(task.state, TaskState::TimedOut);
+    assert!(watcher.sweep(&state, at(200)).await.unwrap().is_empty());
+
+    // The researcher heard about both, and the task refuses new messages
+    let received = state
+        .message_store
+        .query(MessageFilter {
+            recipient_id: Some(researcher.id),
+            ..MessageFilter::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(received.len(), 3);
+    assert!(received[1].content.contains("80%"));
+    assert!(received[2].content.contains("timed out"));
+    server
+        .post("/agent/message")
+        .json(&serde_json::json!({
+            "from": "researcher",
+            "to": "researcher",
+            "content": "late",
+            "task_id": task.id,
+        }))
+        .await
+        .assert_status(axum::http::StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn budget_notices_wait_in_the_orchestrator_inbox() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "__orchestrator__:alice").await;
+    let orchestrator = state
+        .agent_registry
+        .get_by_name("__orchestrator__:alice")
+        .await
+        .unwrap();
+
+    let task = state
+        .task_store
+        .create(CreateTask {
+            title: "Survey".to_string(),
+            created_by: orchestrator.id,
+            parent_task_id: None,
+            time_budget_secs: Some(10),
+        })
+        .await
+        .unwrap();
+    state.task_store.mark_started(task.id).await.unwrap();
+    let started = state.task_store.get(task.id).await.unwrap().started_at.unwrap();
+    let mut watcher = BudgetWatcher::new(BudgetConfig {
+        check_interval: Duration::from_secs(1),
+        warn_at_percent: vec![50],
+        auto_timeout: true,
+    });
+    for secs in [5, 10] {
+        let now = started + chrono::Duration::seconds(secs);
+        assert_eq!(watcher.sweep(&state, now).await.unwrap().len(), 1);
+    }
+
+    // With no session open to hear the notifications, the notices are still
+    // there for the orchestrator to read
+    let inbox = state
+        .message_store
+        .unacknowledged_for(orchestrator.id)
+        .await
+        .unwrap();
+    assert_eq!(inbox.len(), 2);
+    assert!(inbox[0].content.contains("50%"));
+    assert!(inbox[1].content.contains("timed out"));
+    let system = state.agent_registry.get_by_name(SYSTEM_AGENT).await.unwrap();
+    assert!(inbox.iter().all(|m| m.sender_id == system.id && m.task_id == Some(task.id)));
+}
+
+#[tokio::test]
+async fn timed_out_task_notices_are_not_redelivered() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "__orchestrator__:alice").await;
+    register(&server, "researcher").await;
+    let orchestrator = state
+        .agent_registry
+        .get_by_name("__orchestrator__:alice")
+        .await
+        .unwrap();
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+
+    let task = state
+        .task_store
+        .create(CreateTask {
+            title: "Survey".to_string(),
+            created_by: orchestrator.id,
+            parent_task_id: None,
+            time_budget_secs: Some(10),
+        })
+        .await
+        .unwrap();
+    state.task_store.mark_started(task.id).await.unwrap();
+    state
+        .task_store
+        .assign(task.id, researcher.id, TaskRole::Contributor)
+        .await
+        .unwrap();
+    let started = state.task_store.get(task.id).await.unwrap().started_at.unwrap();
+    let mut watcher = BudgetWatcher::new(BudgetConfig {
+        check_interval: Duration::from_secs(1),
+        warn_at_percent: vec![],
+        auto_timeout: true,
+    });
+    let events = watcher
+        .sweep(&state, started + chrono::Duration::seconds(10))
+        .await
+        .unwrap();
+    assert_eq!(events, [(task.id, BudgetEvent::Expired)]);
+
+    // The notice comes from the system agent, so a worker acknowledges it
+    // without attempting a reply, and nothing is left over for redelivery
+    let mut inbox = delivery::open_inbox(&state, &researcher, None)
+        .await
+        .unwrap();
+    let notice = inbox.next().await.unwrap();
+    assert_eq!(notice.sender_name, SYSTEM_AGENT);
+    assert!(notice.message.content.contains("timed out"));
+    server
+        .post("/agent/ack")
+        .json(&serde_json::json!({"agent": "researcher", "message_id": notice.message.id}))
+        .await
+        .assert_status_ok();
+    let config = RedeliveryConfig {
+        visibility_timeout: Duration::ZERO,
+        max_attempts: 5,
+    };
+    assert_eq!(redelivery::redeliver_expired(&state, config).await.unwrap(), 0);
+
+    // Nor is the system agent offered as a worker
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": "list_agents", "arguments": {}}
+        }))
+        .await
+        .json();
+    let agents = body["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(!agents.contains(SYSTEM_AGENT));
+}
+
+#[tokio::test]
+async fn mcp_send_and_wait_returns_correlated_reply() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+    register(&server, "impostor").await;
+    server
+        .post("/mcp")
+        .json(&serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+        .await
+        .assert_status_ok();
+
+    // Play the researcher: answer the first message it receives, after an
+    // impostor answers it first
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+    let impostor = state.agent_registry.get_by_name("impostor").await.unwrap();
+    let mut inbox = delivery::open_inbox(&state, &researcher, None)
+        .await
+        .unwrap();
+    let responder_state = state.clone();
+    tokio::spawn(async move {
+        let question = inbox.next().await.unwrap().message;
+        for (sender_id, content) in [
+            (impostor.id, "Not my question".to_string()),
+            (question.recipient_id, format!("Answer to: {}", question.content)),
+        ] {
+            let reply = responder_state
+                .message_store
+                .create(meddler_core::types::CreateMessage {
+                    sender_id,
+                    recipient_id: question.sender_id,
+                    task_id: None,
+                    in_reply_to: Some(question.id),
+                    content,
+                })
+                .await
+                .unwrap();
+            delivery::dispatch(&responder_state, "__orchestrator__", reply).await;
+        }
+    });
+
+    let resp = server
+        .post("/mcp")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "send_and_wait",
+                "arguments": {"to": "researcher", "content": "why?", "timeout_secs": 5}
+            }
+        }))
+        .await;
+
+    resp.assert_status_ok();
+    let body: serde_json::Value = resp.json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let result: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(result["timed_out"], false);
+    assert_eq!(result["reply"]["from"], "researcher");
+    assert_eq!(result["reply"]["content"], "Answer to: why?");
+}
+
+#[tokio::test]
+async fn mcp_fan_out_reports_progress() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    for name in ["researcher", "writer", "lurker"] {
+        register(&server, name).await;
+    }
+
+    // The researcher answers, the writer is connected but stays silent, and
+    // the lurker isn't connected so isn't asked
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+    let writer = state.agent_registry.get_by_name("writer").await.unwrap();
+    let mut inbox = delivery::open_inbox(&state, &researcher, None).await.unwrap();
+    let _silent = delivery::open_inbox(&state, &writer, None).await.unwrap();
+    let responder_state = state.clone();
+    tokio::spawn(async move {
+        let question = inbox.next().await.unwrap().message;
+        let reply = responder_state
+            .message_store
+            .create(meddler_core::types::CreateMessage {
+                sender_id: question.recipient_id,
+                recipient_id: question.sender_id,
+                task_id: None,
+                in_reply_to: Some(question.id),
+                content: format!("Answer to: {}", question.content),
+            })
+            .await
+            .unwrap();
+        delivery::dispatch(&responder_state, "__orchestrator__", reply).await;
+    });
+
+    let resp = server
+        .post("/mcp")
+        .add_header("accept", "application/json, text/event-stream")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {
+                "name": "fan_out",
+                "arguments": {"content": "why?", "timeout_secs": 1},
+                "_meta": {"progressToken": "fan-1"}
+            }
+        }))
+        .await;
+
+    let events: Vec<serde_json::Value> = resp
+        .text()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    let progress: Vec<&serde_json::Value> = events
+        .iter()
+        .filter(|e| e["method"] == "notifications/progress")
+        .map(|e| &e["params"])
+        .collect();
+    assert_eq!(progress.len(), 2);
+    assert!(progress.iter().all(|p| p["progressToken"] == "fan-1" && p["total"] == 2));
+    assert_eq!(progress[0]["progress"], 0);
+    assert_eq!(progress[1]["progress"], 1);
+    assert_eq!(progress[1]["message"], "1/2 agents responded");
+
+    let response = events.last().unwrap();
+    assert_eq!(response["id"], 3);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    let result: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(result["replies"][0]["from"], "researcher");
+    assert_eq!(result["replies"][0]["content"], "Answer to: why?");
+    assert_eq!(result["timed_out"], serde_json::json!(["writer"]));
+}
+
+#[tokio::test]
+async fn mcp_fan_out_checks_every_recipient_first() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+    register(&server, "writer").await;
+
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "fan_out",
+                "arguments": {"to": ["researcher", "nobody", "writer"], "content": "why?"}
+            }
+        }))
+        .await
+        .json();
+    assert_eq!(body["result"]["isError"], true);
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("agent_not_found"));
+
+    // Nobody was asked, so no reply can turn up that the caller can't place
+    let sent = state
+        .message_store
+        .query(MessageFilter::default())
+        .await
+        .unwrap();
+    assert!(sent.is_empty());
+}
+
+#[tokio::test]
+async fn mcp_tool_calls_can_be_cancelled() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+
+    let resp = server
+        .post("/mcp")
+        .json(&serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"}))
+        .await;
+    let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+
+    let call = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "slow",
+            "method": "tools/call",
+            "params": {
+                "name": "send_and_wait",
+                "arguments": {"to": "researcher", "content": "why?", "timeout_secs": 60}
+            }
+        }));
+    let cancel = async {
+        while state.sessions.pending_replies().await == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        server
+            .post("/mcp")
+            .add_header("mcp-session-id", session_id.as_str())
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": {"requestId": "slow", "reason": "user pressed stop"}
+            }))
+            .await
+            .assert_status(axum::http::StatusCode::ACCEPTED);
+    };
+
+    let (resp, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+        tokio::join!(async { call.await }, cancel)
+    })
+    .await
+    .expect("cancelled call should return promptly");
+
+    // No response for a cancelled request, and no one left waiting for a reply
+    resp.assert_status(axum::http::StatusCode::ACCEPTED);
+    assert_eq!(state.sessions.pending_replies().await, 0);
+}
+
+#[tokio::test]
+async fn mcp_session_lifecycle() {
+    let server = build_test_app();
+
+    let resp = server
+        .post("/mcp")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {}
+        }))
+        .await;
+    resp.assert_status_ok();
+    let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+
+    let tools_list = serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&tools_list)
+        .await
+        .assert_status_ok();
+
+    // The session's version was settled by its initialize
+    let body: serde_json::Value = server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "initialize",
+            "params": {"protocolVersion": "2024-11-05"}
+        }))
+        .await
+        .json();
+    assert_eq!(body["error"]["code"], -32600);
+
+    // Unknown sessions must re-initialize
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", "nope")
+        .json(&tools_list)
+        .await
+        .assert_status_not_found();
+
+    server
+        .delete("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .await
+        .assert_status(axum::http::StatusCode::NO_CONTENT);
+
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_id.as_str())
+        .json(&tools_list)
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn idle_mcp_sessions_are_evicted() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+
+    let initialize = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+    let mut session_ids = Vec::new();
+    for _ in 0..2 {
+        let resp = server.post("/mcp").json(&initialize).await;
+        session_ids.push(resp.header("mcp-session-id").to_str().unwrap().to_string());
+    }
+
+    // Requests keep a session alive, and so does an open stream
+    assert_eq!(state.mcp_sessions.evict_idle(Duration::from_mins(1)).await, 0);
+    let streaming = state.mcp_sessions.get(&session_ids[1]).await.unwrap();
+    let _stream = streaming.subscribe();
+    assert_eq!(state.mcp_sessions.evict_idle(Duration::ZERO).await, 1);
+
+    let tools_list = serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_ids[0].as_str())
+        .json(&tools_list)
+        .await
+        .assert_status_not_found();
+    server
+        .post("/mcp")
+        .add_header("mcp-session-id", session_ids[1].as_str())
+        .json(&tools_list)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn mcp_requests_without_a_session_are_served_statelessly() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+
+    // Clients that never initialize act as the default orchestrator, or the
+    // one their header names, and get no session
+    let send = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {"name": "send_message", "arguments": {"to": "researcher", "content": "hi"}}
+    });
+    let resp = server.post("/mcp").json(&send).await;
+    resp.assert_status_ok();
+    assert!(resp.maybe_header("mcp-session-id").is_none());
+    let resp = server
+        .post("/mcp")
+        .add_header("x-meddler-orchestrator", "script")
+        .json(&send)
+        .await;
+    resp.assert_status_ok();
+
+    let senders: Vec<String> = {
+        let mut names = Vec::new();
+        for message in state.message_store.query(MessageFilter::default()).await.unwrap() {
+            let sender = state.agent_registry.get_by_id(message.sender_id).await.unwrap();
+            names.push(sender.name);
+        }
+        names
+    };
+    assert_eq!(senders, ["__orchestrator__", "__orchestrator__:script"]);
+    assert!(!state.mcp_sessions.has_orchestrator("__orchestrator__").await);
+}
+
+#[tokio::test]
+async fn mcp_stream_requires_session() {
+    let server = build_test_app();
+    server.get("/mcp").await.assert_status_bad_request();
+    server
+        .get("/mcp")
+        .add_header("mcp-session-id", "nope")
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn mcp_stream_resumes_after_last_event_id() {
+    use axum::response::IntoResponse;
+
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    let initialize = serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+    let resp = server.post("/mcp").json(&initialize).await;
+    let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+    let session = state.mcp_sessions.get(&session_id).await.unwrap();
+
+    // Sent while the client was away
+    for n in 1..=3 {
+        session.notify("notifications/message", serde_json::json!({"n": n}));
+    }
+
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("mcp-session-id", session_id.parse().unwrap());
+    headers.insert("last-event-id", "1".parse().unwrap());
+    let stream = meddler_server::handlers::mcp_stream(axum::extract::State(state.clone()), headers)
+        .await
+        .unwrap();
+    let mut body = stream.into_response().into_body().into_data_stream();
+    session.notify("notifications/message", serde_json::json!({"n": 4}));
+
+    // The events after the client's last one are replayed, then live ones
+    // follow with the next id
+    let mut events = Vec::new();
+    while events.len() < 3 {
+        let chunk = tokio::time::timeout(Duration::from_secs(1), body.next());
+        let chunk = chunk.await.unwrap().unwrap().unwrap();
+        let text = String::from_utf8(chunk.to_vec()).unwrap();
+        for block in text.split("\n\n").filter(|block| !block.is_empty()) {
+            let field = |name| block.lines().find_map(|line| line.strip_prefix(name)).unwrap();
+            let data: serde_json::Value = serde_json::from_str(field("data: ")).unwrap();
+            events.push((field("id: ").to_string(), data["params"]["n"].clone()));
+        }
+    }
+    let expected: Vec<(String, serde_json::Value)> =
+        (2..=4).map(|n| (n.to_string(), n.into())).collect();
+    assert_eq!(events, expected);
+}
+
+#[tokio::test]
+async fn mcp_tools_call_upgrades_to_event_stream() {
+    let server = build_test_app();
+    register(&server, "researcher").await;
+
+    let resp = server
+        .post("/mcp")
+        .add_header("accept", "application/json, text/event-stream")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": {
+                "name": "send_and_wait",
+                "arguments": {"to": "researcher", "content": "hi", "timeout_secs": 0}
+            }
+        }))
+        .await;
+
+    resp.assert_status_ok();
+    let content_type = resp.header("content-type");
+    assert!(content_type.to_str().unwrap().starts_with("text/event-stream"));
+
+    // A log notification about the wait, then the response itself
+    let events: Vec<serde_json::Value> = resp
+        .text()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["method"], "notifications/message");
+    assert_eq!(events[1]["id"], 7);
+    assert!(events[1].get("result").is_some());
+}
+
+#[tokio::test]
+async fn mcp_sessions_have_separate_orchestrators() {
+    let state = build_test_state();
+    let server = build_test_server(state.clone());
+    register(&server, "researcher").await;
+
+    let initialize = |client: &str| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"clientInfo": {"name": client, "version": "1.0"}}
+        })
+    };
+    let cursor = server
+        .post("/mcp")
+        .add_header("x-meddler-orchestrator", "alice")
+        .json(&initialize("cursor"))
+        .await;
+    let zed = server.post("/mcp").json(&initialize("zed")).await;
+
+    let ask = |content: &str| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "send_message",
+                "arguments": {"to": "researcher", "content": content}
+            }
+        })
+    };
+    for (resp, question) in [(&cursor, "from cursor"), (&zed, "from zed")] {
+        let session_id = resp.header("mcp-session-id").to_str().unwrap().to_string();
+        server
+            .post("/mcp")
+            .add_header("mcp-session-id", session_id.as_str())
+            .json(&ask(question))
+            .await
+            .assert_status_ok();
+    }
+
+    // The researcher answers whoever asked
+    let researcher = state.agent_registry.get_by_name("researcher").await.unwrap();
+    let mut inbox = delivery::open_inbox(&state, &researcher, None)
+        .await
+        .unwrap();
+    let mut askers = Vec::new();
+    for _ in 0..2 {
+        let question = inbox.next().await.unwrap();
+        let reply = format!("re: {}", question.message.content);
+        send(&server, "researcher", &question.sender_name, &reply).await;
+        askers.push(question.sender_name);
+    }
+
+    assert_eq!(askers[0], "__orchestrator__:alice");
+    assert!(askers[1].starts_with("__orchestrator__:zed-"));
+    for (asker, reply) in askers.iter().zip(["re: from cursor", "re: from zed"]) {
+        let orchestrator = state.agent_registry.get_by_name(asker).await.unwrap();
+        let replies = state
+            .message_store
+            .query(MessageFilter {
+                recipient_id: Some(orchestrator.id),
+                ..MessageFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].content, reply);
+    }
+
+    // Orchestrators are not workers to delegate to
+    let resp = server
+        .post("/mcp")
+        .json(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {"name": "list_agents", "arguments": {}}
+        }))
+        .await;
+    let body: serde_json::Value = resp.json();
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let result: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(result["agents"].as_array().unwrap().len(), 1);
 }
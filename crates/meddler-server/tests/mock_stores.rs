@@ -1,14 +1,15 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::RwLock;
 
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 
 use meddler_core::error::Error;
 use meddler_core::traits::{AgentRegistry, MessageStore, TaskStore};
 use meddler_core::types::{
-    Agent, AgentId, CreateMessage, CreateTask, Message, MessageFilter, MessageId, RegisterAgent,
-    Task, TaskId, TaskStatus,
+    Agent, AgentId, CreateMessage, CreateTask, DeliveryState, Message, MessageFilter, MessageId,
+    MessageOrder, RegisterAgent, Task, TaskAssignment, TaskId, TaskRole, TaskState, TaskStatus,
 };
 
 /// In-memory mock agent registry.
@@ -59,6 +60,15 @@ impl AgentRegistry for MockAgentRegistry {
             .ok_or(Error::AgentNotFoundById(id))
     }
 
+    async fn get_by_ids(&self, ids: &[AgentId]) -> Result<Vec<Agent>, Error> {
+        let agents = self.agents.read().unwrap();
+        Ok(agents
+            .values()
+            .filter(|a| ids.contains(&a.id))
+            .cloned()
+            .collect())
+    }
+
     async fn list(&self) -> Result<Vec<Agent>, Error> {
         let agents = self.agents.read().unwrap();
         Ok(agents.values().cloned().collect())
@@ -73,6 +83,9 @@ impl AgentRegistry for MockAgentRegistry {
 #[derive(Default)]
 pub struct MockMessageStore {
     messages: RwLock<Vec<Message>>,
+    next_seq: AtomicI64,
+    /// Message id -> when it was last delivered.
+    deliveries: RwLock<HashMap<MessageId, DateTime<Utc>>>,
 }
 
 impl MockMessageStore {
@@ -86,33 +99,170 @@ impl MessageStore for MockMessageStore {
     async fn create(&self, params: CreateMessage) -> Result<Message, Error> {
         let message = Message {
             id: MessageId::new(),
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst) + 1,
             sender_id: params.sender_id,
             recipient_id: params.recipient_id,
             task_id: params.task_id,
+            in_reply_to: params.in_reply_to,
             content: params.content,
+            delivery_state: DeliveryState::Pending,
+            delivery_attempts: 0,
             created_at: Utc::now(),
         };
         self.messages.write().unwrap().push(message.clone());
         Ok(message)
     }
 
+    async fn get_message(&self, id: MessageId) -> Result<Message, Error> {
+        let messages = self.messages.read().unwrap();
+        messages
+            .iter()
+            .find(|m| m.id == id)
+            .cloned()
+            .ok_or(Error::MessageNotFound(id))
+    }
+
     async fn query(&self, filter: MessageFilter) -> Result<Vec<Message>, Error> {
         let messages = self.messages.read().unwrap();
-        let result = messages
+        let mut result: Vec<Message> = messages
             .iter()
             .filter(|m| filter.task_id.is_none() || m.task_id == filter.task_id)
             .filter(|m| filter.sender_id.is_none_or(|id| m.sender_id == id))
             .filter(|m| filter.recipient_id.is_none_or(|id| m.recipient_id == id))
+            .filter(|m| filter.in_reply_to.is_none() || m.in_reply_to == filter.in_reply_to)
+            .filter(|m| filter.after_seq.is_none_or(|seq| m.seq > seq))
+            .filter(|m| filter.before_seq.is_none_or(|seq| m.seq < seq))
+            .filter(|m| {
+                filter.between.is_none_or(|(a, b)| {
+                    (m.sender_id, m.recipient_id) == (a, b)
+                        || (m.sender_id, m.recipient_id) == (b, a)
+                })
+            })
+            .filter(|m| filter.since.is_none_or(|t| m.created_at >= t))
+            .filter(|m| filter.until.is_none_or(|t| m.created_at < t))
+            .filter(|m| filter.text.as_deref().is_none_or(|text| matches_text(m, text)))
             .cloned()
             .collect();
+        result.sort_by_key(|m| m.seq);
+        if filter.order == MessageOrder::NewestFirst {
+            result.reverse();
+        }
+        if let Some(limit) = filter.limit {
+            result.truncate(limit as usize);
+        }
         Ok(result)
     }
+
+    async fn count(&self, filter: MessageFilter) -> Result<u64, Error> {
+        let all = MessageFilter {
+            limit: None,
+            ..filter
+        };
+        Ok(self.query(all).await?.len() as u64)
+    }
+
+    async fn task_participants(&self, task_id: TaskId) -> Result<Vec<AgentId>, Error> {
+        let messages = self.messages.read().unwrap();
+        let mut ids: Vec<AgentId> = messages
+            .iter()
+            .filter(|m| m.task_id == Some(task_id))
+            .flat_map(|m| [m.sender_id, m.recipient_id])
+            .collect();
+        ids.sort_by_key(|id| id.0);
+        ids.dedup();
+        Ok(ids)
+    }
+
+    async fn backlog_for(
+        &self,
+        recipient_id: AgentId,
+        after_seq: Option<i64>,
+    ) -> Result<Vec<Message>, Error> {
+        let messages = self.messages.read().unwrap();
+        Ok(messages
+            .iter()
+            .filter(|m| m.recipient_id == recipient_id)
+            .filter(|m| {
+                m.delivery_state == DeliveryState::Pending || after_seq.is_some_and(|s| m.seq > s)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn unacknowledged_for(&self, recipient_id: AgentId) -> Result<Vec<Message>, Error> {
+        let messages = self.messages.read().unwrap();
+        Ok(messages
+            .iter()
+            .filter(|m| m.recipient_id == recipient_id)
+            .filter(|m| m.delivery_state != DeliveryState::Acknowledged)
+            .cloned()
+            .collect())
+    }
+
+    async fn mark_delivered(&self, id: MessageId) -> Result<(), Error> {
+        let mut messages = self.messages.write().unwrap();
+        if let Some(message) = messages
+            .iter_mut()
+            .find(|m| m.id == id && m.delivery_state == DeliveryState::Pending)
+        {
+            message.delivery_state = DeliveryState::Delivered;
+            message.delivery_attempts += 1;
+            self.deliveries.write().unwrap().insert(id, Utc::now());
+        }
+        Ok(())
+    }
+
+    async fn acknowledge(&self, id: MessageId, recipient_id: AgentId) -> Result<Message, Error> {
+        let mut messages = self.messages.write().unwrap();
+        let message = messages
+            .iter_mut()
+            .find(|m| m.id == id && m.recipient_id == recipient_id)
+            .ok_or(Error::MessageNotFound(id))?;
+        message.delivery_state = DeliveryState::Acknowledged;
+        Ok(message.clone())
+    }
+
+    async fn requeue_unacknowledged(
+        &self,
+        delivered_before: DateTime<Utc>,
+        max_attempts: i32,
+    ) -> Result<Vec<Message>, Error> {
+        let deliveries = self.deliveries.read().unwrap();
+        let mut messages = self.messages.write().unwrap();
+        let mut requeued = Vec::new();
+        for message in messages.iter_mut() {
+            let Some(&delivered_at) = deliveries.get(&message.id) else {
+                continue;
+            };
+            if message.delivery_state == DeliveryState::Delivered
+                && delivered_at < delivered_before
+                && message.delivery_attempts < max_attempts
+            {
+                message.delivery_state = DeliveryState::Pending;
+                requeued.push(message.clone());
+            }
+        }
+        Ok(requeued)
+    }
+}
+
+/// Stand-in for Postgres full-text search: every word of the query must
+/// appear in the content, ignoring case. `-word` excludes a word.
+fn matches_text(message: &Message, text: &str) -> bool {
+    let content = message.content.to_lowercase();
+    text.to_lowercase()
+        .split_whitespace()
+        .all(|word| match word.strip_prefix('-') {
+            Some(excluded) => !content.contains(excluded),
+            None => content.contains(word),
+        })
 }
 
 /// In-memory mock task store.
 #[derive(Default)]
 pub struct MockTaskStore {
     tasks: RwLock<HashMap<TaskId, Task>>,
+    assignments: RwLock<Vec<TaskAssignment>>,
 }
 
 impl MockTaskStore {
@@ -124,12 +274,23 @@ impl MockTaskStore {
 #[async_trait]
 impl TaskStore for MockTaskStore {
     async fn create(&self, params: CreateTask) -> Result<Task, Error> {
+        let time_budget_secs = match params.parent_task_id {
+            Some(parent) => self
+                .get_status(parent)
+                .await?
+                .subtask_budget(params.time_budget_secs)?,
+            None => params.time_budget_secs,
+        };
         let task = Task {
             id: TaskId::new(),
             title: params.title,
             created_by: params.created_by,
-            time_budget_secs: params.time_budget_secs,
+            parent_task_id: params.parent_task_id,
+            time_budget_secs,
+            state: TaskState::Pending,
             started_at: None,
+            completed_at: None,
+            outcome: None,
             created_at: Utc::now(),
         };
         self.tasks.write().unwrap().insert(task.id, task.clone());
@@ -141,6 +302,38 @@ impl TaskStore for MockTaskStore {
         tasks.get(&id).cloned().ok_or(Error::TaskNotFound(id))
     }
 
+    async fn list(&self) -> Result<Vec<Task>, Error> {
+        let mut tasks: Vec<Task> = self.tasks.read().unwrap().values().cloned().collect();
+        tasks.sort_by_key(|task| std::cmp::Reverse(task.created_at));
+        Ok(tasks)
+    }
+
+    async fn list_in_state(&self, state: TaskState) -> Result<Vec<Task>, Error> {
+        let mut tasks: Vec<Task> = self
+            .tasks
+            .read()
+            .unwrap()
+            .values()
+            .filter(|task| task.state == state)
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|task| task.created_at);
+        Ok(tasks)
+    }
+
+    async fn list_subtasks(&self, parent: TaskId) -> Result<Vec<Task>, Error> {
+        let mut tasks: Vec<Task> = self
+            .tasks
+            .read()
+            .unwrap()
+            .values()
+            .filter(|task| task.parent_task_id == Some(parent))
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|task| task.created_at);
+        Ok(tasks)
+    }
+
     async fn get_status(&self, id: TaskId) -> Result<TaskStatus, Error> {
         let task = self.get(id).await?;
         Ok(TaskStatus::compute(task, Utc::now()))
@@ -148,11 +341,85 @@ impl TaskStore for MockTaskStore {
 
     async fn mark_started(&self, id: TaskId) -> Result<(), Error> {
         let mut tasks = self.tasks.write().unwrap();
-        if let Some(task) = tasks.get_mut(&id) {
-            if task.started_at.is_none() {
-                task.started_at = Some(Utc::now());
-            }
+        let task = tasks.get_mut(&id).ok_or(Error::TaskNotFound(id))?;
+        if task.state.is_closed() {
+            return Err(Error::TaskClosed(id, task.state));
         }
+        task.state = TaskState::Running;
+        task.started_at.get_or_insert_with(Utc::now);
         Ok(())
     }
+
+    async fn transition(
+        &self,
+        id: TaskId,
+        state: TaskState,
+        outcome: Option<String>,
+    ) -> Result<Task, Error> {
+        let mut tasks = self.tasks.write().unwrap();
+        let task = tasks.get_mut(&id).ok_or(Error::TaskNotFound(id))?;
+        if !task.state.can_transition_to(state) {
+            return Err(Error::InvalidTaskTransition {
+                task: id,
+                from: task.state,
+                to: state,
+            });
+        }
+        task.state = state;
+        if state == TaskState::Running {
+            task.started_at.get_or_insert_with(Utc::now);
+        }
+        if state.is_closed() {
+            task.completed_at = Some(Utc::now());
+        }
+        if outcome.is_some() {
+            task.outcome = outcome;
+        }
+        Ok(task.clone())
+    }
+
+    async fn assign(
+        &self,
+        task_id: TaskId,
+        agent_id: AgentId,
+        role: TaskRole,
+    ) -> Result<TaskAssignment, Error> {
+        let task = self.get(task_id).await?;
+        if task.state.is_closed() {
+            return Err(Error::TaskClosed(task_id, task.state));
+        }
+
+        let mut assignments = self.assignments.write().unwrap();
+        if let Some(existing) = assignments
+            .iter_mut()
+            .find(|a| a.task_id == task_id && a.agent_id == agent_id)
+        {
+            existing.role = role;
+            return Ok(existing.clone());
+        }
+        let assignment = TaskAssignment {
+            task_id,
+            agent_id,
+            role,
+            assigned_at: Utc::now(),
+        };
+        assignments.push(assignment.clone());
+        Ok(assignment)
+    }
+
+    async fn unassign(&self, task_id: TaskId, agent_id: AgentId) -> Result<bool, Error> {
+        let mut assignments = self.assignments.write().unwrap();
+        let before = assignments.len();
+        assignments.retain(|a| !(a.task_id == task_id && a.agent_id == agent_id));
+        Ok(assignments.len() < before)
+    }
+
+    async fn list_assignments(&self, task_id: TaskId) -> Result<Vec<TaskAssignment>, Error> {
+        let assignments = self.assignments.read().unwrap();
+        Ok(assignments
+            .iter()
+            .filter(|a| a.task_id == task_id)
+            .cloned()
+            .collect())
+    }
 }
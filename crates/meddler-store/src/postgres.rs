@@ -1,11 +1,13 @@
 use async_trait::async_trait;
-use sqlx::PgPool;
+use sqlx::postgres::PgArguments;
+use sqlx::query::QueryAs;
+use sqlx::{PgPool, Postgres};
 
 use meddler_core::error::Error;
 use meddler_core::traits::{AgentRegistry, MessageStore, TaskStore};
 use meddler_core::types::{
-    Agent, AgentId, CreateMessage, CreateTask, Message, MessageFilter, MessageId, RegisterAgent,
-    Task, TaskId, TaskStatus,
+    Agent, AgentId, CreateMessage, CreateTask, Message, MessageFilter, MessageId, MessageOrder,
+    RegisterAgent, Task, TaskAssignment, TaskId, TaskRole, TaskState, TaskStatus,
 };
 
 /// Postgres-backed implementation of all storage traits.
@@ -82,6 +84,23 @@ impl AgentRegistry for PgStore {
         Ok(row.into())
     }
 
+    async fn get_by_ids(&self, ids: &[AgentId]) -> Result<Vec<Agent>, Error> {
+        let ids: Vec<uuid::Uuid> = ids.iter().map(|id| id.0).collect();
+        let rows = sqlx::query_as::<_, AgentRow>(
+            r"
+            SELECT id, name, description, registered_at, last_seen_at
+            FROM agents
+            WHERE id = ANY($1)
+            ",
+        )
+        .bind(ids)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
     async fn list(&self) -> Result<Vec<Agent>, Error> {
         let rows = sqlx::query_as::<_, AgentRow>(
             "SELECT id, name, description, registered_at, last_seen_at FROM agents ORDER BY name",
@@ -109,70 +128,245 @@ impl MessageStore for PgStore {
         let id = uuid::Uuid::new_v4();
         let row = sqlx::query_as::<_, MessageRow>(
             r"
-            INSERT INTO messages (id, sender_id, recipient_id, task_id, content)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, sender_id, recipient_id, task_id, content, created_at
+            INSERT INTO messages (id, sender_id, recipient_id, task_id, in_reply_to, content)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
             ",
         )
         .bind(id)
         .bind(params.sender_id.0)
         .bind(params.recipient_id.0)
         .bind(params.task_id.map(|t| t.0))
+        .bind(params.in_reply_to.map(|m| m.0))
         .bind(&params.content)
         .fetch_one(&self.pool)
         .await
         .map_err(|e| Error::Database(e.to_string()))?;
 
-        Ok(row.into())
+        row.try_into()
+    }
+
+    async fn get_message(&self, id: MessageId) -> Result<Message, Error> {
+        let row = sqlx::query_as::<_, MessageRow>(
+            r"
+            SELECT id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
+            FROM messages
+            WHERE id = $1
+            ",
+        )
+        .bind(id.0)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?
+        .ok_or(Error::MessageNotFound(id))?;
+
+        row.try_into()
     }
 
     async fn query(&self, filter: MessageFilter) -> Result<Vec<Message>, Error> {
+        let order = match filter.order {
+            MessageOrder::OldestFirst => "ASC",
+            MessageOrder::NewestFirst => "DESC",
+        };
+        let sql = format!(
+            r"
+            SELECT id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
+            FROM messages
+            WHERE {MESSAGE_FILTER}
+            ORDER BY seq {order}
+            LIMIT $12
+            "
+        );
+        let rows = bind_message_filter(sqlx::query_as::<_, MessageRow>(&sql), &filter)
+            .bind(filter.limit.map(i64::from))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn count(&self, filter: MessageFilter) -> Result<u64, Error> {
+        let sql = format!("SELECT COUNT(*) FROM messages WHERE {MESSAGE_FILTER}");
+        let (count,) = bind_message_filter(sqlx::query_as::<_, (i64,)>(&sql), &filter)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(count.unsigned_abs())
+    }
+
+    async fn task_participants(&self, task_id: TaskId) -> Result<Vec<AgentId>, Error> {
+        let ids: Vec<uuid::Uuid> = sqlx::query_scalar(
+            r"
+            SELECT sender_id FROM messages WHERE task_id = $1
+            UNION
+            SELECT recipient_id FROM messages WHERE task_id = $1
+            ",
+        )
+        .bind(task_id.0)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(ids.into_iter().map(AgentId).collect())
+    }
+
+    async fn backlog_for(
+        &self,
+        recipient_id: AgentId,
+        after_seq: Option<i64>,
+    ) -> Result<Vec<Message>, Error> {
         let rows = sqlx::query_as::<_, MessageRow>(
             r"
-            SELECT id, sender_id, recipient_id, task_id, content, created_at
+            SELECT id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
             FROM messages
-            WHERE ($1::uuid IS NULL OR task_id = $1)
-              AND ($2::uuid IS NULL OR sender_id = $2)
-              AND ($3::uuid IS NULL OR recipient_id = $3)
-            ORDER BY created_at ASC
+            WHERE recipient_id = $1
+              AND (delivery_state = 'pending' OR seq > $2)
+            ORDER BY seq ASC
             ",
         )
-        .bind(filter.task_id.map(|t| t.0))
-        .bind(filter.sender_id.map(|a| a.0))
-        .bind(filter.recipient_id.map(|a| a.0))
+        .bind(recipient_id.0)
+        .bind(after_seq)
         .fetch_all(&self.pool)
         .await
         .map_err(|e| Error::Database(e.to_string()))?;
 
-        Ok(rows.into_iter().map(Into::into).collect())
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn unacknowledged_for(&self, recipient_id: AgentId) -> Result<Vec<Message>, Error> {
+        let rows = sqlx::query_as::<_, MessageRow>(
+            r"
+            SELECT id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
+            FROM messages
+            WHERE recipient_id = $1
+              AND delivery_state <> 'acknowledged'
+            ORDER BY seq ASC
+            ",
+        )
+        .bind(recipient_id.0)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn mark_delivered(&self, id: MessageId) -> Result<(), Error> {
+        sqlx::query(
+            r"
+            UPDATE messages
+            SET delivery_state = 'delivered',
+                delivered_at = NOW(),
+                delivery_attempts = delivery_attempts + 1
+            WHERE id = $1 AND delivery_state = 'pending'
+            ",
+        )
+        .bind(id.0)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn acknowledge(&self, id: MessageId, recipient_id: AgentId) -> Result<Message, Error> {
+        let row = sqlx::query_as::<_, MessageRow>(
+            r"
+            UPDATE messages
+            SET delivery_state = 'acknowledged',
+                acknowledged_at = COALESCE(acknowledged_at, NOW())
+            WHERE id = $1 AND recipient_id = $2
+            RETURNING id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
+            ",
+        )
+        .bind(id.0)
+        .bind(recipient_id.0)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?
+        .ok_or(Error::MessageNotFound(id))?;
+
+        row.try_into()
+    }
+
+    async fn requeue_unacknowledged(
+        &self,
+        delivered_before: chrono::DateTime<chrono::Utc>,
+        max_attempts: i32,
+    ) -> Result<Vec<Message>, Error> {
+        let rows = sqlx::query_as::<_, MessageRow>(
+            r"
+            UPDATE messages
+            SET delivery_state = 'pending', delivered_at = NULL
+            WHERE delivery_state = 'delivered'
+              AND delivered_at < $1
+              AND delivery_attempts < $2
+            RETURNING id, seq, sender_id, recipient_id, task_id, in_reply_to, content, delivery_state,
+                   delivery_attempts, created_at
+            ",
+        )
+        .bind(delivered_before)
+        .bind(max_attempts)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        let mut messages = rows
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<Message>, Error>>()?;
+        messages.sort_by_key(|m| m.seq);
+        Ok(messages)
     }
 }
 
 #[async_trait]
 impl TaskStore for PgStore {
     async fn create(&self, params: CreateTask) -> Result<Task, Error> {
+        let time_budget_secs = match params.parent_task_id {
+            Some(parent) => self
+                .get_status(parent)
+                .await?
+                .subtask_budget(params.time_budget_secs)?,
+            None => params.time_budget_secs,
+        };
+
         let id = uuid::Uuid::new_v4();
         let row = sqlx::query_as::<_, TaskRow>(
             r"
-            INSERT INTO tasks (id, title, created_by, time_budget_secs)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, title, created_by, time_budget_secs, started_at, created_at
+            INSERT INTO tasks (id, title, created_by, parent_task_id, time_budget_secs)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, title, created_by, parent_task_id, time_budget_secs, state, started_at,
+                      completed_at, outcome, created_at
             ",
         )
         .bind(id)
         .bind(&params.title)
         .bind(params.created_by.0)
-        .bind(params.time_budget_secs)
+        .bind(params.parent_task_id.map(|t| t.0))
+        .bind(time_budget_secs)
         .fetch_one(&self.pool)
         .await
         .map_err(|e| Error::Database(e.to_string()))?;
 
-        Ok(row.into())
+        row.try_into()
     }
 
     async fn get(&self, id: TaskId) -> Result<Task, Error> {
         let row = sqlx::query_as::<_, TaskRow>(
-            "SELECT id, title, created_by, time_budget_secs, started_at, created_at FROM tasks WHERE id = $1",
+            r"
+            SELECT id, title, created_by, parent_task_id, time_budget_secs, state, started_at,
+                   completed_at, outcome, created_at
+            FROM tasks
+            WHERE id = $1
+            ",
         )
         .bind(id.0)
         .fetch_optional(&self.pool)
@@ -180,7 +374,59 @@ impl TaskStore for PgStore {
         .map_err(|e| Error::Database(e.to_string()))?
         .ok_or(Error::TaskNotFound(id))?;
 
-        Ok(row.into())
+        row.try_into()
+    }
+
+    async fn list(&self) -> Result<Vec<Task>, Error> {
+        let rows = sqlx::query_as::<_, TaskRow>(
+            r"
+            SELECT id, title, created_by, parent_task_id, time_budget_secs, state, started_at,
+                   completed_at, outcome, created_at
+            FROM tasks
+            ORDER BY created_at DESC
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn list_in_state(&self, state: TaskState) -> Result<Vec<Task>, Error> {
+        let rows = sqlx::query_as::<_, TaskRow>(
+            r"
+            SELECT id, title, created_by, parent_task_id, time_budget_secs, state, started_at,
+                   completed_at, outcome, created_at
+            FROM tasks
+            WHERE state = $1
+            ORDER BY created_at ASC
+            ",
+        )
+        .bind(state.as_str())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn list_subtasks(&self, parent: TaskId) -> Result<Vec<Task>, Error> {
+        let rows = sqlx::query_as::<_, TaskRow>(
+            r"
+            SELECT id, title, created_by, parent_task_id, time_budget_secs, state, started_at,
+                   completed_at, outcome, created_at
+            FROM tasks
+            WHERE parent_task_id = $1
+            ORDER BY created_at ASC
+            ",
+        )
+        .bind(parent.0)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
     }
 
     async fn get_status(&self, id: TaskId) -> Result<TaskStatus, Error> {
@@ -189,15 +435,169 @@ impl TaskStore for PgStore {
     }
 
     async fn mark_started(&self, id: TaskId) -> Result<(), Error> {
-        sqlx::query(
-            "UPDATE tasks SET started_at = NOW() WHERE id = $1 AND started_at IS NULL",
+        let updated = sqlx::query(
+            r"
+            UPDATE tasks
+            SET started_at = COALESCE(started_at, NOW()),
+                state = 'running'
+            WHERE id = $1 AND state IN ('pending', 'running')
+            ",
         )
         .bind(id.0)
         .execute(&self.pool)
         .await
         .map_err(|e| Error::Database(e.to_string()))?;
+
+        if updated.rows_affected() == 0 {
+            // Either the task doesn't exist or it has closed
+            let task = self.get(id).await?;
+            return Err(Error::TaskClosed(id, task.state));
+        }
         Ok(())
     }
+
+    async fn transition(
+        &self,
+        id: TaskId,
+        state: TaskState,
+        outcome: Option<String>,
+    ) -> Result<Task, Error> {
+        let task = self.get(id).await?;
+        if !task.state.can_transition_to(state) {
+            return Err(Error::InvalidTaskTransition {
+                task: id,
+                from: task.state,
+                to: state,
+            });
+        }
+
+        // Only move from the state we validated, in case it changed meanwhile
+        let row = sqlx::query_as::<_, TaskRow>(
+            r"
+            UPDATE tasks
+            SET state = $2,
+                outcome = COALESCE($3, outcome),
+                started_at = CASE WHEN $2 = 'running' THEN COALESCE(started_at, NOW())
+                                  ELSE started_at END,
+                completed_at = CASE WHEN $4 THEN NOW() ELSE completed_at END
+            WHERE id = $1 AND state = $5
+            RETURNING id, title, created_by, parent_task_id, time_budget_secs, state, started_at,
+                      completed_at, outcome, created_at
+            ",
+        )
+        .bind(id.0)
+        .bind(state.as_str())
+        .bind(outcome)
+        .bind(state.is_closed())
+        .bind(task.state.as_str())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        match row {
+            Some(row) => row.try_into(),
+            None => Err(Error::InvalidTaskTransition {
+                task: id,
+                from: self.get(id).await?.state,
+                to: state,
+            }),
+        }
+    }
+
+    async fn assign(
+        &self,
+        task_id: TaskId,
+        agent_id: AgentId,
+        role: TaskRole,
+    ) -> Result<TaskAssignment, Error> {
+        let task = self.get(task_id).await?;
+        if task.state.is_closed() {
+            return Err(Error::TaskClosed(task_id, task.state));
+        }
+
+        let row = sqlx::query_as::<_, AssignmentRow>(
+            r"
+            INSERT INTO task_assignments (task_id, agent_id, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (task_id, agent_id) DO UPDATE
+                SET role = EXCLUDED.role
+            RETURNING task_id, agent_id, role, assigned_at
+            ",
+        )
+        .bind(task_id.0)
+        .bind(agent_id.0)
+        .bind(role.as_str())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        row.try_into()
+    }
+
+    async fn unassign(&self, task_id: TaskId, agent_id: AgentId) -> Result<bool, Error> {
+        let deleted = sqlx::query(
+            "DELETE FROM task_assignments WHERE task_id = $1 AND agent_id = $2",
+        )
+        .bind(task_id.0)
+        .bind(agent_id.0)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    async fn list_assignments(&self, task_id: TaskId) -> Result<Vec<TaskAssignment>, Error> {
+        let rows = sqlx::query_as::<_, AssignmentRow>(
+            r"
+            SELECT task_id, agent_id, role, assigned_at
+            FROM task_assignments
+            WHERE task_id = $1
+            ORDER BY assigned_at ASC
+            ",
+        )
+        .bind(task_id.0)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
+
+/// `WHERE` clause matching a [`MessageFilter`], with parameters `$1` to `$11`
+/// bound by [`bind_message_filter`]. Limit and order are left to the caller.
+const MESSAGE_FILTER: &str = r"
+    ($1::uuid IS NULL OR task_id = $1)
+    AND ($2::uuid IS NULL OR sender_id = $2)
+    AND ($3::uuid IS NULL OR recipient_id = $3)
+    AND ($4::uuid IS NULL OR in_reply_to = $4)
+    AND ($5::bigint IS NULL OR seq > $5)
+    AND ($6::bigint IS NULL OR seq < $6)
+    AND ($7::uuid IS NULL
+         OR (sender_id = $7 AND recipient_id = $8)
+         OR (sender_id = $8 AND recipient_id = $7))
+    AND ($9::timestamptz IS NULL OR created_at >= $9)
+    AND ($10::timestamptz IS NULL OR created_at < $10)
+    AND ($11::text IS NULL OR content_tsv @@ websearch_to_tsquery('english', $11))
+";
+
+fn bind_message_filter<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    filter: &MessageFilter,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(filter.task_id.map(|t| t.0))
+        .bind(filter.sender_id.map(|a| a.0))
+        .bind(filter.recipient_id.map(|a| a.0))
+        .bind(filter.in_reply_to.map(|m| m.0))
+        .bind(filter.after_seq)
+        .bind(filter.before_seq)
+        .bind(filter.between.map(|(a, _)| a.0))
+        .bind(filter.between.map(|(_, b)| b.0))
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.text.clone())
 }
 
 // --- Internal row types for sqlx ---
@@ -226,23 +626,33 @@ impl From<AgentRow> for Agent {
 #[derive(sqlx::FromRow)]
 struct MessageRow {
     id: uuid::Uuid,
+    seq: i64,
     sender_id: uuid::Uuid,
     recipient_id: uuid::Uuid,
     task_id: Option<uuid::Uuid>,
+    in_reply_to: Option<uuid::Uuid>,
     content: String,
+    delivery_state: String,
+    delivery_attempts: i32,
     created_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl From<MessageRow> for Message {
-    fn from(row: MessageRow) -> Self {
-        Self {
+impl TryFrom<MessageRow> for Message {
+    type Error = Error;
+
+    fn try_from(row: MessageRow) -> Result<Self, Self::Error> {
+        Ok(Self {
             id: MessageId(row.id),
+            seq: row.seq,
             sender_id: AgentId(row.sender_id),
             recipient_id: AgentId(row.recipient_id),
             task_id: row.task_id.map(TaskId),
+            in_reply_to: row.in_reply_to.map(MessageId),
             content: row.content,
+            delivery_state: row.delivery_state.parse().map_err(Error::Database)?,
+            delivery_attempts: row.delivery_attempts,
             created_at: row.created_at,
-        }
+        })
     }
 }
 
@@ -251,20 +661,51 @@ struct TaskRow {
     id: uuid::Uuid,
     title: String,
     created_by: uuid::Uuid,
+    parent_task_id: Option<uuid::Uuid>,
     time_budget_secs: Option<i64>,
+    state: String,
     started_at: Option<chrono::DateTime<chrono::Utc>>,
+    completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    outcome: Option<String>,
     created_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl From<TaskRow> for Task {
-    fn from(row: TaskRow) -> Self {
-        Self {
+impl TryFrom<TaskRow> for Task {
+    type Error = Error;
+
+    fn try_from(row: TaskRow) -> Result<Self, Self::Error> {
+        Ok(Self {
             id: TaskId(row.id),
             title: row.title,
             created_by: AgentId(row.created_by),
+            parent_task_id: row.parent_task_id.map(TaskId),
             time_budget_secs: row.time_budget_secs,
+            state: row.state.parse().map_err(Error::Database)?,
             started_at: row.started_at,
+            completed_at: row.completed_at,
+            outcome: row.outcome,
             created_at: row.created_at,
-        }
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AssignmentRow {
+    task_id: uuid::Uuid,
+    agent_id: uuid::Uuid,
+    role: String,
+    assigned_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<AssignmentRow> for TaskAssignment {
+    type Error = Error;
+
+    fn try_from(row: AssignmentRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            task_id: TaskId(row.task_id),
+            agent_id: AgentId(row.agent_id),
+            role: row.role.parse().map_err(Error::Database)?,
+            assigned_at: row.assigned_at,
+        })
     }
 }
@@ -0,0 +1,11 @@
+-- Track delivery of each message to its recipient. Existing rows are
+-- backfilled as delivered so upgrading doesn't replay old history.
+ALTER TABLE messages
+    ADD COLUMN delivery_state TEXT NOT NULL DEFAULT 'delivered'
+        CHECK (delivery_state IN ('pending', 'delivered', 'acknowledged')),
+    ADD COLUMN delivered_at TIMESTAMPTZ;
+
+ALTER TABLE messages ALTER COLUMN delivery_state SET DEFAULT 'pending';
+
+CREATE INDEX idx_messages_pending ON messages(recipient_id, created_at)
+    WHERE delivery_state = 'pending';
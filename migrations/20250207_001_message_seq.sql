@@ -0,0 +1,19 @@
+-- Monotonic cursor over messages, used as the SSE event id so reconnecting
+-- clients can resume with Last-Event-ID. Existing rows are numbered in
+-- creation order.
+ALTER TABLE messages ADD COLUMN seq BIGINT;
+
+UPDATE messages m
+SET seq = ordered.rn
+FROM (SELECT id, row_number() OVER (ORDER BY created_at, id) AS rn FROM messages) ordered
+WHERE m.id = ordered.id;
+
+CREATE SEQUENCE messages_seq_seq OWNED BY messages.seq;
+SELECT setval('messages_seq_seq', COALESCE((SELECT MAX(seq) FROM messages), 0) + 1, false);
+
+ALTER TABLE messages
+    ALTER COLUMN seq SET DEFAULT nextval('messages_seq_seq'),
+    ALTER COLUMN seq SET NOT NULL;
+
+CREATE UNIQUE INDEX idx_messages_seq ON messages(seq);
+CREATE INDEX idx_messages_recipient_seq ON messages(recipient_id, seq);
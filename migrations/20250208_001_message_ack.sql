@@ -0,0 +1,8 @@
+-- Explicit acknowledgement by the recipient, and bookkeeping for redelivery
+-- of messages that were delivered but never acknowledged.
+ALTER TABLE messages
+    ADD COLUMN acknowledged_at TIMESTAMPTZ,
+    ADD COLUMN delivery_attempts INT NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_messages_unacked ON messages(delivered_at)
+    WHERE delivery_state = 'delivered';
@@ -0,0 +1,6 @@
+-- Correlate replies with the message they answer.
+ALTER TABLE messages
+    ADD COLUMN in_reply_to UUID REFERENCES messages(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_messages_in_reply_to ON messages(in_reply_to)
+    WHERE in_reply_to IS NOT NULL;
@@ -0,0 +1,7 @@
+-- Full-text search over message content, and time-range queries.
+ALTER TABLE messages
+    ADD COLUMN content_tsv TSVECTOR
+        GENERATED ALWAYS AS (to_tsvector('english', content)) STORED;
+
+CREATE INDEX idx_messages_content_tsv ON messages USING GIN (content_tsv);
+CREATE INDEX idx_messages_created_at ON messages(created_at);
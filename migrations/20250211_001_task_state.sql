@@ -0,0 +1,8 @@
+-- Task lifecycle. Tasks that already had messages are running.
+ALTER TABLE tasks
+    ADD COLUMN state TEXT NOT NULL DEFAULT 'pending'
+        CHECK (state IN ('pending', 'running', 'completed', 'failed', 'cancelled')),
+    ADD COLUMN completed_at TIMESTAMPTZ,
+    ADD COLUMN outcome TEXT;
+
+UPDATE tasks SET state = 'running' WHERE started_at IS NOT NULL;
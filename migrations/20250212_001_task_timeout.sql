@@ -0,0 +1,7 @@
+-- Tasks that run out of their time budget are closed as timed out.
+ALTER TABLE tasks
+    DROP CONSTRAINT tasks_state_check,
+    ADD CONSTRAINT tasks_state_check
+        CHECK (state IN ('pending', 'running', 'completed', 'failed', 'cancelled', 'timed_out'));
+
+CREATE INDEX idx_tasks_running ON tasks(created_at) WHERE state = 'running';
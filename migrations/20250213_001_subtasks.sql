@@ -0,0 +1,6 @@
+-- Tasks can be broken down into subtasks.
+ALTER TABLE tasks
+    ADD COLUMN parent_task_id UUID REFERENCES tasks(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_tasks_parent ON tasks(parent_task_id, created_at)
+    WHERE parent_task_id IS NOT NULL;
@@ -0,0 +1,10 @@
+-- Which agents work on a task, and in what role.
+CREATE TABLE task_assignments (
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    agent_id UUID NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'contributor', 'reviewer')),
+    assigned_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (task_id, agent_id)
+);
+
+CREATE INDEX idx_task_assignments_agent ON task_assignments(agent_id);